@@ -0,0 +1,302 @@
+use std::{env, error::Error};
+
+use crate::{
+    app_builder::AppBuilder,
+    config::{Database, Feature},
+    manifest::Manifest,
+    merge::merge3,
+    project::Project,
+    template::Templates,
+    util::{append_missing_env_keys, gofmt},
+};
+
+type AddError = Box<dyn Error>;
+
+/// Turns `feature` on in the project in the current directory. Files the
+/// user never touched are regenerated, new files are created, and files
+/// that were edited since vapp wrote them are merged with the new version.
+/// Edited files vapp has no base copy of are left alone with the new
+/// version written next to them as `<file>.vapp-new`.
+pub fn add_feature(feature: Feature, templates: &Templates) -> Result<(), AddError> {
+    let cur_path_buf = env::current_dir()?;
+    let cur_path = match cur_path_buf.to_str() {
+        Some(s) => s.to_string(),
+        None => return Err("cur path is not a valid string".into()),
+    };
+    let project = Project::open(&cur_path)?;
+    if feature.is_enabled(&project.config) {
+        return Err(format!("{} is already enabled", feature.name()).into());
+    }
+    let old = AppBuilder::for_project(project.config.clone(), &project.path, templates)?;
+    let mut config = project.config.clone();
+    feature.enable(&mut config);
+    // switching databases would leave the old dialect's migrations and data behind
+    if project.config.database != Database::None && config.database != project.config.database {
+        return Err(format!(
+            "the project already uses {}, vapp can't switch it to {}",
+            project.config.database.name(),
+            config.database.name()
+        )
+        .into());
+    }
+    let new = AppBuilder::for_project(config, &project.path, templates)?;
+    let root = new.path_to_project();
+
+    for dir in new.dirs() {
+        std::fs::create_dir_all(format!("{}/{}", root, dir))?;
+    }
+
+    let mut conflicts: Vec<(String, usize)> = Vec::new();
+    let mut left_alone: Vec<String> = Vec::new();
+    // files whose new version was merged in or written next to them
+    let mut rebased: Vec<String> = Vec::new();
+    for (file, text) in new.files() {
+        let path = format!("{}/{}", root, file);
+        let current = match std::fs::read_to_string(&path) {
+            Ok(current) => current,
+            Err(_) => {
+                std::fs::write(&path, text)?;
+                println!("created {}", file);
+                continue;
+            }
+        };
+        if old.files().get(file) == Some(text) || same_source(file, &current, text) {
+            continue;
+        }
+        let untouched = match &project.manifest {
+            Some(manifest) => manifest.is_unchanged(file, &current),
+            None => match old.files().get(file) {
+                Some(old_text) => same_source(file, &current, old_text),
+                None => false,
+            },
+        };
+        let update = update_file(root, file, &current, text, untouched)?;
+        match update {
+            Update::Unchanged => (),
+            Update::Written => println!("updated {}", file),
+            Update::Merged(0) => println!("merged {}", file),
+            Update::Merged(n) => conflicts.push((file.clone(), n)),
+            Update::LeftAlone => left_alone.push(file.clone()),
+        }
+        if matches!(update, Update::Merged(_) | Update::LeftAlone) {
+            rebased.push(file.clone());
+        }
+    }
+
+    let old_steps: Vec<String> = old.steps().iter().map(|s| s.command_line()).collect();
+    for step in new.steps() {
+        if step.rerun || !old_steps.contains(&step.command_line()) {
+            new.run_step(root, &step)?;
+        }
+    }
+
+    // the manifest records what vapp generated, so files that differ from
+    // that on disk keep their old record (or get the generated version's,
+    // when the new version was merged in or written next to them) and
+    // still show up as edited by the user
+    let mut manifest = new.manifest(root);
+    for (file, text) in new.files() {
+        let current = std::fs::read_to_string(format!("{}/{}", root, file)).unwrap_or_default();
+        if same_source(file, &current, text) {
+            continue;
+        }
+        let old_record = project.manifest.as_ref().and_then(|m| m.files.get(file));
+        match old_record {
+            Some(hash) if !rebased.contains(file) => manifest.keep(file, hash),
+            _ => manifest.record(file, &formatted(file, text)),
+        }
+    }
+    manifest.write(root)?;
+
+    if !conflicts.is_empty() {
+        println!("these files have conflicts to resolve:");
+        for (file, count) in &conflicts {
+            println!(
+                "    {} ({} conflict{})",
+                file,
+                count,
+                if *count == 1 { "" } else { "s" }
+            );
+        }
+    }
+    if !left_alone.is_empty() {
+        println!("these files were changed since vapp generated them, so they were left alone:");
+        for file in &left_alone {
+            println!("    {} (new version in {}.vapp-new)", file, file);
+        }
+    }
+    if !conflicts.is_empty() || !left_alone.is_empty() {
+        println!("merge the changes by hand and run `go mod tidy` again");
+    }
+    println!("added {}", feature.name());
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Update {
+    Unchanged,
+    Written,
+    /// Merged with the version on disk, leaving this many conflicts.
+    Merged(usize),
+    /// Written next to the file as `<file>.vapp-new`.
+    LeftAlone,
+}
+
+/// Brings `file`, currently `current` on disk, up to date with the newly
+/// generated `text`. `untouched` says whether the user has edited it since
+/// vapp last wrote it.
+fn update_file(
+    root: &str,
+    file: &str,
+    current: &str,
+    text: &str,
+    untouched: bool,
+) -> Result<Update, AddError> {
+    let path = format!("{}/{}", root, file);
+    let updated = match file {
+        ".gitignore" => append_missing_lines(current, text),
+        ".env" => append_missing_env_keys(current, text),
+        _ if untouched => text.to_string(),
+        _ => match Manifest::read_base(root, file) {
+            Some(base) => {
+                let merged = merge3(&base, current, &formatted(file, text), "yours", "vapp");
+                std::fs::write(&path, &merged.text)?;
+                return Ok(Update::Merged(merged.conflicts));
+            }
+            None => {
+                std::fs::write(format!("{}.vapp-new", path), text)?;
+                return Ok(Update::LeftAlone);
+            }
+        },
+    };
+    if updated == current {
+        return Ok(Update::Unchanged);
+    }
+    std::fs::write(&path, updated)?;
+    return Ok(Update::Written);
+}
+
+fn formatted(file: &str, text: &str) -> String {
+    if file.ends_with(".go") {
+        if let Some(res) = gofmt(text) {
+            return res;
+        }
+    }
+    return text.to_string();
+}
+
+/// Whether two versions of a file are the same once `go fmt` has had its
+/// say, since the generated go files are formatted after being written.
+fn same_source(file: &str, a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    if !file.ends_with(".go") {
+        return false;
+    }
+    match (gofmt(a), gofmt(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn append_missing_lines(current: &str, wanted: &str) -> String {
+    let mut res = current.to_string();
+    for line in wanted.lines() {
+        if line.trim().is_empty() || current.lines().any(|l| l.trim() == line.trim()) {
+            continue;
+        }
+        if !res.is_empty() && !res.ends_with('\n') {
+            res.push('\n');
+        }
+        res += line;
+        res.push('\n');
+    }
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::BASE_DIR;
+
+    fn project(name: &str) -> String {
+        let root = env::temp_dir().join(format!("vapp-add-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        return root.to_str().unwrap().to_string();
+    }
+
+    fn read(root: &str, file: &str) -> String {
+        return std::fs::read_to_string(format!("{}/{}", root, file)).unwrap();
+    }
+
+    #[test]
+    fn overwrites_untouched_files() {
+        let root = project("untouched");
+        std::fs::write(format!("{}/notes.txt", root), "old\n").unwrap();
+        let update = update_file(&root, "notes.txt", "old\n", "new\n", true).unwrap();
+        assert_eq!(update, Update::Written);
+        assert_eq!(read(&root, "notes.txt"), "new\n");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn merges_edited_files_with_their_base() {
+        let root = project("merged");
+        std::fs::create_dir_all(format!("{}/{}", root, BASE_DIR)).unwrap();
+        std::fs::write(
+            format!("{}/{}/notes.txt", root, BASE_DIR),
+            "a\nb\nc\nd\ne\n",
+        )
+        .unwrap();
+        let current = "a\nmine\nc\nd\ne\n";
+        std::fs::write(format!("{}/notes.txt", root), current).unwrap();
+        let update = update_file(&root, "notes.txt", current, "a\nb\nc\nd\nvapp\n", false).unwrap();
+        assert_eq!(update, Update::Merged(0));
+        assert_eq!(read(&root, "notes.txt"), "a\nmine\nc\nd\nvapp\n");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn leaves_edited_files_without_a_base_alone() {
+        let root = project("left-alone");
+        std::fs::write(format!("{}/notes.txt", root), "mine\n").unwrap();
+        let update = update_file(&root, "notes.txt", "mine\n", "vapp\n", false).unwrap();
+        assert_eq!(update, Update::LeftAlone);
+        assert_eq!(read(&root, "notes.txt"), "mine\n");
+        assert_eq!(read(&root, "notes.txt.vapp-new"), "vapp\n");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn appends_missing_env_keys_and_ignores() {
+        let root = project("append");
+        let env = "PORT=8080\nDB_URL=mine\n";
+        std::fs::write(format!("{}/.env", root), env).unwrap();
+        let wanted = "PORT=3000\nDB_URL=vapp\nSESSION_KEY=abc\nREDIS_URL=redis\n";
+        let update = update_file(&root, ".env", env, wanted, false).unwrap();
+        assert_eq!(update, Update::Written);
+        assert_eq!(
+            read(&root, ".env"),
+            "PORT=8080\nDB_URL=mine\nSESSION_KEY=abc\nREDIS_URL=redis\n"
+        );
+
+        let ignore = "bin/\n.env\n";
+        std::fs::write(format!("{}/.gitignore", root), ignore).unwrap();
+        let update = update_file(
+            &root,
+            ".gitignore",
+            ignore,
+            "bin/\n.env\n\ntmp/\n*.db\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(update, Update::Written);
+        assert_eq!(read(&root, ".gitignore"), "bin/\n.env\ntmp/\n*.db\n");
+
+        let update = update_file(&root, ".gitignore", "bin/\n", "bin/\n", false).unwrap();
+        assert_eq!(update, Update::Unchanged);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+use std::error::Error;
+
+use crate::{
+    config::{Config, ConfigBuilder, Database, Framework, SessionStore},
+    htmx,
+};
+
+type AnswersError = Box<dyn Error>;
+
+/// The schema version written to new answers files. Bump this whenever an
+/// option is renamed or changes meaning, and teach `parse` how to read the
+/// older versions. Adding a new option does not need a bump: keys that are
+/// missing from a file are simply left for the prompts (or `--yes`) to fill.
+///
+/// Version 2 replaced `turso = <bool>` with `database = "<name>"`.
+pub const ANSWERS_VERSION: i64 = 2;
+
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Bool(bool),
+    Int(i64),
+}
+
+pub fn read_answers(path: &str) -> Result<ConfigBuilder, AnswersError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read answers file {}: {}", path, e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+pub fn write_answers(path: &str, config: &Config) -> Result<(), AnswersError> {
+    std::fs::write(path, to_toml(config))
+        .map_err(|e| format!("failed to write answers file {}: {}", path, e))?;
+    Ok(())
+}
+
+pub fn to_toml(config: &Config) -> String {
+    let mut res = String::from("# vapp answers file, use with `vapp new --answers <file>`\n");
+    res += &format!("version = {}\n\n", ANSWERS_VERSION);
+    res += &format!("app_name = {}\n", quote(&config.app_name));
+    if let Some(module_path) = &config.module_path {
+        res += &format!("module_path = {}\n", quote(module_path));
+    }
+    res += &format!("framework = {}\n", quote(config.framework.name()));
+    res += &format!("templ = {}\n", config.templ);
+    res += &format!("sessions = {}\n", config.sessions);
+    if config.sessions {
+        res += &format!("session_store = {}\n", quote(config.session_store.name()));
+    }
+    res += &format!("database = {}\n", quote(config.database.name()));
+    res += &format!("auth = {}\n", config.auth);
+    res += &format!("htmx = {}\n", config.htmx);
+    if config.htmx {
+        res += &format!("htmx_version = {}\n", quote(&config.htmx_version));
+        res += &format!(
+            "htmx_extensions = {}\n",
+            quote(&config.htmx_extensions.join(","))
+        );
+    }
+    res += &format!("tailwind = {}\n", config.tailwind);
+    res += &format!("air = {}\n", config.air);
+    return res;
+}
+
+fn parse(text: &str) -> Result<ConfigBuilder, AnswersError> {
+    let mut builder = ConfigBuilder::new();
+    let mut version = None;
+    for (i, raw_line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), parse_value(v.trim(), line_no)?),
+            None => return Err(format!("line {}: expected `key = value`", line_no).into()),
+        };
+        builder = match (key, value) {
+            ("version", Value::Int(v)) => {
+                version = Some(v);
+                builder
+            }
+            ("app_name", Value::Str(s)) => builder.add_app_name(s),
+            ("module_path", Value::Str(s)) => builder.add_module_path(s),
+            ("framework", Value::Str(s)) => match Framework::from_name(&s) {
+                Some(framework) => builder.add_framework(framework),
+                None => return Err(format!("line {}: unknown framework {:?}", line_no, s).into()),
+            },
+            ("templ", Value::Bool(b)) => builder.add_templ(b),
+            ("sessions", Value::Bool(b)) => builder.add_sessions(b),
+            ("session_store", Value::Str(s)) => match SessionStore::from_name(&s) {
+                Some(store) => builder.add_session_store(store),
+                None => {
+                    return Err(format!("line {}: unknown session store {:?}", line_no, s).into())
+                }
+            },
+            ("database", Value::Str(s)) => match Database::from_name(&s) {
+                Some(database) => builder.add_database(database),
+                None => return Err(format!("line {}: unknown database {:?}", line_no, s).into()),
+            },
+            // version 1 only knew turso
+            ("turso", Value::Bool(true)) => builder.add_database(Database::Turso),
+            ("turso", Value::Bool(false)) => builder.add_database(Database::None),
+            ("auth", Value::Bool(b)) => builder.add_auth(b),
+            ("htmx", Value::Bool(b)) => builder.add_htmx(b),
+            ("htmx_version", Value::Str(s)) => {
+                if !htmx::VERSIONS.contains(&s.as_str()) {
+                    return Err(format!("line {}: unknown htmx version {:?}", line_no, s).into());
+                }
+                builder.add_htmx_version(s)
+            }
+            ("htmx_extensions", Value::Str(s)) => {
+                let extensions: Vec<String> = s
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                if let Some(name) = extensions
+                    .iter()
+                    .find(|name| !htmx::EXTENSIONS.contains(&name.as_str()))
+                {
+                    return Err(
+                        format!("line {}: unknown htmx extension {:?}", line_no, name).into(),
+                    );
+                }
+                builder.add_htmx_extensions(extensions)
+            }
+            ("tailwind", Value::Bool(b)) => builder.add_tailwind(b),
+            ("air", Value::Bool(b)) => builder.add_air(b),
+            (key, value) => {
+                return Err(format!("line {}: unexpected {} = {:?}", line_no, key, value).into())
+            }
+        };
+    }
+    match version {
+        Some(v) if (1..=ANSWERS_VERSION).contains(&v) => Ok(builder),
+        Some(v) => Err(format!(
+            "answers file version {} is newer than this vapp supports ({})",
+            v, ANSWERS_VERSION
+        )
+        .into()),
+        None => Err("missing `version` key".into()),
+    }
+}
+
+fn parse_value(value: &str, line_no: usize) -> Result<Value, AnswersError> {
+    let value = strip_comment(value);
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return unquote(inner)
+            .map(Value::Str)
+            .ok_or_else(|| format!("line {}: invalid string {}", line_no, value).into());
+    }
+    match value {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => match value.parse::<i64>() {
+            Ok(v) => Ok(Value::Int(v)),
+            Err(_) => Err(format!("line {}: invalid value {}", line_no, value).into()),
+        },
+    }
+}
+
+/// Drops a trailing `# comment`, leaving `#` inside a quoted string alone.
+fn strip_comment(value: &str) -> &str {
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, ch) in value.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '#' if !in_str => return value[..i].trim_end(),
+            _ => (),
+        }
+    }
+    return value;
+}
+
+fn quote(s: &str) -> String {
+    let mut res = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            '\t' => res += "\\t",
+            _ => res.push(ch),
+        }
+    }
+    res.push('"');
+    return res;
+}
+
+fn unquote(s: &str) -> Option<String> {
+    let mut res = String::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next()? {
+                '"' => res.push('"'),
+                '\\' => res.push('\\'),
+                'n' => res.push('\n'),
+                't' => res.push('\t'),
+                _ => return None,
+            },
+            '"' => return None,
+            _ => res.push(ch),
+        }
+    }
+    return Some(res);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_config() {
+        let config = ConfigBuilder::new()
+            .add_app_name("app".to_string())
+            .add_module_path("example.com/\"odd\\path".to_string())
+            .add_framework(Framework::Chi)
+            .add_sessions(true)
+            .add_session_store(SessionStore::Redis)
+            .add_database(Database::Postgres)
+            .add_htmx(true)
+            .add_htmx_extensions(vec!["sse".to_string(), "ws".to_string()])
+            .out();
+        let parsed = parse(&to_toml(&config)).unwrap().out();
+        assert_eq!(to_toml(&parsed), to_toml(&config));
+        assert_eq!(
+            parsed.module_path.as_deref(),
+            Some("example.com/\"odd\\path")
+        );
+    }
+
+    #[test]
+    fn reads_version_1_turso() {
+        let builder = parse("version = 1\napp_name = \"app\"\nturso = true\n").unwrap();
+        assert_eq!(builder.database(), Some(Database::Turso));
+        let builder = parse("version = 1\nturso = false\n").unwrap();
+        assert_eq!(builder.database(), Some(Database::None));
+    }
+
+    #[test]
+    fn strips_comments_but_not_inside_strings() {
+        let text = "# answers\nversion = 2 # current\napp_name = \"a#b\" # the name\n";
+        let builder = parse(text).unwrap();
+        assert_eq!(builder.app_name(), Some("a#b"));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse("app_name = \"app\"\n").is_err());
+        assert!(parse("version = 99\n").is_err());
+        assert!(parse("version = 2\napp_name\n").is_err());
+        assert!(parse("version = 2\napp_name = \"a\"b\"\n").is_err());
+        assert!(parse("version = 2\napp_name = \"a\\qb\"\n").is_err());
+        assert!(parse("version = 2\nauth = \"yes\"\n").is_err());
+        assert!(parse("version = 2\ndatabase = \"mysql\"\n").is_err());
+        assert!(parse("version = 2\nhtmx_extensions = \"sse,foo\"\n").is_err());
+    }
+
+    #[test]
+    fn quote_escapes_what_unquote_reads() {
+        let s = "a \"b\" \\c\nd\te";
+        let quoted = quote(s);
+        assert_eq!(quoted, "\"a \\\"b\\\" \\\\c\\nd\\te\"");
+        assert!(!quoted.contains('\n'));
+        assert_eq!(unquote(&quoted[1..quoted.len() - 1]).as_deref(), Some(s));
+    }
+}
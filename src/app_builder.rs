@@ -1,8 +1,13 @@
-use std::{env, error::Error, io::Write, process::Command};
+use std::{collections::BTreeMap, env, error::Error, io::Write, path::Path, process::Command};
 
 use crate::{
-    config::Config,
-    util::{get_exit_code, get_git_username},
+    config::{Config, Database, Framework, SessionStore},
+    htmx,
+    json::Json,
+    manifest::Manifest,
+    template::{Context, Templates, Value},
+    util::{get_exit_code, get_git_username, random_hex},
+    validate::validate_files,
 };
 
 type AppBuilderError = Box<dyn Error>;
@@ -11,33 +16,66 @@ type AppBuilderError = Box<dyn Error>;
 struct AppBuilderConfig {
     mod_name: String,
     path_to_project: String,
+    path_to_staging: String,
     config: Config,
     dirs_to_create: Vec<String>,
-    file_to_text_map: std::collections::HashMap<String, String>,
+    file_to_text_map: BTreeMap<String, String>,
+    /// The vendored htmx and its extensions, when htmx is on.
+    scripts: Vec<htmx::Script>,
 }
 
 impl AppBuilderConfig {
-    pub fn new(config: Config) -> Result<Self, AppBuilderError> {
+    pub fn new(config: Config, templates: &Templates) -> Result<Self, AppBuilderError> {
         let cur_path_buf = env::current_dir()?;
         let cur_path = match cur_path_buf.to_str() {
             Some(s) => s.to_string(),
             None => return Err("cur path is not a valid string".into()),
         };
-        let path_to_project = cur_path + "/" + &config.app_name;
-        let git_user_name = get_git_username();
-        let mod_name = match &git_user_name {
-            Some(name) => format!("github.com/{}/{}", name, config.app_name),
-            None => config.app_name.clone(),
+        let path_to_project = format!("{}/{}", cur_path, config.app_name);
+        return Self::at(config, path_to_project, templates);
+    }
+
+    fn at(
+        config: Config,
+        path_to_project: String,
+        templates: &Templates,
+    ) -> Result<Self, AppBuilderError> {
+        let path_to_staging = match path_to_project.rsplit_once('/') {
+            Some((parent, name)) => format!("{}/.{}.vapp-staging", parent, name),
+            None => format!(".{}.vapp-staging", path_to_project),
+        };
+        if config.sessions
+            && config.session_store == SessionStore::Db
+            && config.database == Database::None
+        {
+            return Err("the db session store needs a database, pick one with --database".into());
+        }
+        if config.auth && (!config.sessions || config.database == Database::None) {
+            return Err("auth needs sessions and a database, add them first".into());
+        }
+        let mod_name = match &config.module_path {
+            Some(module_path) => module_path.clone(),
+            None => match get_git_username() {
+                Some(name) => format!("github.com/{}/{}", name, config.app_name),
+                None => config.app_name.clone(),
+            },
+        };
+        let scripts = if config.htmx {
+            htmx::scripts(&config.htmx_version, &config.htmx_extensions)?
+        } else {
+            Vec::new()
         };
         let mut res = Self {
             mod_name,
             path_to_project,
+            path_to_staging,
             config,
             dirs_to_create: Vec::new(),
-            file_to_text_map: std::collections::HashMap::new(),
+            file_to_text_map: BTreeMap::new(),
+            scripts,
         };
         res.add_dirs_to_create();
-        res.init_file_to_text_map();
+        res.init_file_to_text_map(templates)?;
         Ok(res)
     }
 
@@ -45,427 +83,781 @@ impl AppBuilderConfig {
         let first_letter = self.config.app_name.as_bytes()[0] as char;
         let custom_ctx_name = format!("{}ctx", first_letter);
         let mut needed = vec![
-            self.path_to_project.clone(),
-            format!("{}/cmd", self.path_to_project),
-            format!("{}/cmd/{}", self.path_to_project, self.config.app_name),
-            format!("{}/internal", self.path_to_project),
-            format!("{}/internal/routes", self.path_to_project),
-            format!("{}/internal/{}", self.path_to_project, custom_ctx_name),
-            format!("{}/internal/env", self.path_to_project),
-            format!("{}/internal/render", self.path_to_project),
-            format!("{}/public", self.path_to_project),
+            "cmd".to_string(),
+            format!("cmd/{}", self.config.app_name),
+            "internal".to_string(),
+            "internal/routes".to_string(),
+            format!("internal/{}", custom_ctx_name),
+            "internal/env".to_string(),
+            "internal/render".to_string(),
+            "public".to_string(),
         ];
-        if self.config.turso {
-            let db_dir = format!("{}/testdb", self.path_to_project);
-            let db_lib_dir = format!("{}/internal/db", self.path_to_project);
-            needed.push(db_dir);
-            needed.push(db_lib_dir);
+        if self.config.templ {
+            needed.push("internal/views".to_string());
+        } else {
+            needed.push("public/layouts".to_string());
+            needed.push("public/partials".to_string());
+        }
+        if let Some((dir, _)) = self.local_db_file() {
+            needed.push(dir.to_string());
+        }
+        if self.config.database != Database::None {
+            needed.push("internal/db".to_string());
+            needed.push(MIGRATIONS_DIR.to_string());
         }
         if self.config.tailwind {
-            let css_dir = format!("{}/css", self.path_to_project);
-            needed.push(css_dir);
+            needed.push("css".to_string());
+        }
+        if self.vendors_htmx() {
+            needed.push("public/js".to_string());
+            if !self.config.htmx_extensions.is_empty() {
+                needed.push("public/js/ext".to_string());
+            }
         }
         self.dirs_to_create = needed;
     }
 
-    fn init_file_to_text_map(&mut self) {
+    fn init_file_to_text_map(&mut self, templates: &Templates) -> Result<(), AppBuilderError> {
         let first_letter = self.config.app_name.as_bytes()[0] as char;
         let custom_ctx_name = format!("{}ctx", first_letter);
         let mut needed = vec![
+            ("main.go".to_string(), "main_go"),
+            (".gitignore".to_string(), "gitignore"),
+            (".env".to_string(), "env"),
+            (".env.example".to_string(), "env"),
+            ("Makefile".to_string(), "makefile"),
+            ("internal/routes/root.go".to_string(), "root_go"),
             (
-                format!("{}/main.go", self.path_to_project),
-                self.get_main_go_text_content(),
-            ),
-            (
-                format!("{}/.gitignore", self.path_to_project),
-                self.get_gitignore_text_content(),
-            ),
-            (
-                format!("{}/.env", self.path_to_project),
-                self.get_dot_env_text_content(),
-            ),
-            (
-                format!("{}/Makefile", self.path_to_project),
-                self.get_makefile_text_content(),
-            ),
-            (
-                format!("{}/internal/routes/root.go", self.path_to_project),
-                self.get_root_go_text_content(),
-            ),
-            (
-                format!(
-                    "{}/internal/{}/{}.go",
-                    self.path_to_project, custom_ctx_name, custom_ctx_name
-                ),
-                self.get_custom_ctx_text_content(),
-            ),
-            (
-                format!("{}/internal/env/env.go", self.path_to_project),
-                self.get_env_text_content(),
+                format!("internal/{}/{}.go", custom_ctx_name, custom_ctx_name),
+                "custom_ctx_go",
             ),
+            ("internal/env/env.go".to_string(), "env_go"),
+            ("internal/render/render.go".to_string(), "render_go"),
             (
-                format!("{}/internal/render/render.go", self.path_to_project),
-                self.get_render_go_text_content(),
-            ),
-            (
-                format!("{}/public/index.html", self.path_to_project),
-                self.get_index_html_text_content(),
-            ),
-            (
-                format!(
-                    "{}/cmd/{}/main.go",
-                    self.path_to_project, self.config.app_name
-                ),
-                self.get_cmd_main_go_text_content(),
+                format!("cmd/{}/main.go", self.config.app_name),
+                "cmd_main_go",
             ),
         ];
 
-        if self.config.turso {
-            let db_file = (
-                format!("{}/internal/db/db.go", self.path_to_project),
-                self.get_db_go_text_content(),
-            );
-            needed.push(db_file);
+        if self.config.templ {
+            needed.push(("internal/views/layout.templ".to_string(), "layout_templ"));
+            needed.push(("internal/views/index.templ".to_string(), "index_templ"));
+            if self.uses_csrf() {
+                needed.push(("internal/views/csrf.go".to_string(), "views_csrf_go"));
+            }
+            if self.config.air {
+                // air init knows nothing about templ, so vapp writes the config
+                needed.push((".air.toml".to_string(), "air_toml"));
+            }
+        } else {
+            needed.push(("public/layouts/base.html".to_string(), "base_layout_html"));
+            needed.push(("public/partials/head.html".to_string(), "head_html"));
+            needed.push(("public/index.html".to_string(), "index_html"));
         }
 
-        if self.config.tailwind {
-            let css_file = (
-                format!("{}/css/index.css", self.path_to_project),
-                self.get_css_text_content(),
-            );
-            needed.push(css_file);
+        if self.config.database != Database::None {
+            needed.push(("internal/db/db.go".to_string(), "db_go"));
+            needed.push(("internal/db/migrate.go".to_string(), "migrate_go"));
+            needed.push((format!("{}/0001_init.sql", MIGRATIONS_DIR), "init_sql"));
+        }
+
+        if self.config.auth {
+            needed.push((self.users_migration_file(), "users_migration_sql"));
+            needed.push(("internal/db/users.go".to_string(), "users_go"));
+            needed.push((
+                format!("internal/{}/auth.go", custom_ctx_name),
+                "ctx_auth_go",
+            ));
+            needed.push(("internal/routes/auth.go".to_string(), "auth_routes_go"));
+            if self.config.templ {
+                needed.push(("internal/views/auth.templ".to_string(), "auth_templ"));
+            } else {
+                needed.push(("public/login.html".to_string(), "login_html"));
+                needed.push(("public/register.html".to_string(), "register_html"));
+            }
         }
 
-        for (key, val) in needed {
-            self.file_to_text_map.insert(key, val);
+        if self.uses_session_store(SessionStore::Db) {
+            needed.push((
+                "internal/db/session_store.go".to_string(),
+                "session_store_go",
+            ));
         }
-    }
 
-    fn get_main_go_text_content(&self) -> String {
-        let template = include_str!("text/main_go");
-        let mut res = template.replace("##name##", &self.config.app_name);
-        res = res.replace(
-            "##mod_name##",
-            &format!("{}/cmd/{}", self.mod_name, self.config.app_name),
-        );
-        return res;
-    }
+        if self.uses_csrf() && self.config.framework != Framework::Echo {
+            needed.push((format!("internal/{}/csrf.go", custom_ctx_name), "csrf_go"));
+        }
 
-    fn get_gitignore_text_content(&self) -> String {
-        let mut res = String::from("bin\n\n.env\n\n");
-        if self.config.turso {
-            res += "testdb\n\n";
+        if self.config.database == Database::Postgres
+            || self.uses_session_store(SessionStore::Redis)
+        {
+            needed.push(("docker-compose.yml".to_string(), "docker_compose_yml"));
         }
+
         if self.config.tailwind {
-            res += "public/css\n\n";
+            needed.push(("css/index.css".to_string(), "index_css"));
+        }
+
+        if self.uses_public_fs() {
+            needed.push(("public/public.go".to_string(), "public_go"));
         }
-        if self.config.air {
-            res += "tmp\n\n";
+
+        let ctx = self.get_template_context();
+        for (key, template_name) in needed {
+            let text = templates.render(template_name, &ctx)?;
+            self.file_to_text_map.insert(key, text);
         }
-        return res;
+        if self.config.sessions {
+            // .env.example is committed and keeps the placeholder
+            let mut secret_ctx = ctx.clone();
+            secret_ctx.insert("session_secret".to_string(), Value::Str(random_hex(64)?));
+            let text = templates.render("env", &secret_ctx)?;
+            self.file_to_text_map.insert(".env".to_string(), text);
+        }
+        validate_files(&self.file_to_text_map, &ctx)?;
+        if self.vendors_htmx() {
+            for script in &self.scripts {
+                self.file_to_text_map
+                    .insert(format!("public/{}", script.path), script.text.to_string());
+            }
+        }
+        Ok(())
     }
 
-    fn get_dot_env_text_content(&self) -> String {
-        let res = include_str!("text/env").to_string();
-        return res;
+    /// The migration creating the users table: the one already in the
+    /// project, or the next free number so it runs after the existing ones.
+    fn users_migration_file(&self) -> String {
+        let root = &self.path_to_project;
+        let name = match find_migration(root, "_create_users.sql") {
+            Some(name) => name,
+            None => format!(
+                "{:04}_create_users.sql",
+                last_migration_number(root).max(1) + 1
+            ),
+        };
+        return format!("{}/{}", MIGRATIONS_DIR, name);
     }
 
-    fn get_makefile_text_content(&self) -> String {
-        let mut res = String::from(".PHONY: all\n");
-        res += "all:\n";
-        res += "\tgo build -o bin/main\n\n";
-        if self.config.air {
-            res += ".PHONY: dev\n";
-            res += ".dev:\n";
-            res += "\tair";
-            if self.config.tailwind {
-                res += " & pnpm css\n\n";
-            } else {
-                res += "\n\n";
-            }
-        }
-        return res;
+    /// Forms are protected against CSRF whenever there is a session to
+    /// protect or htmx sends requests on its own.
+    fn uses_csrf(&self) -> bool {
+        self.config.sessions || self.config.htmx
     }
 
-    fn get_root_go_text_content(&self) -> String {
-        let first_letter = self.config.app_name.as_bytes()[0] as char;
-        let custom_ctx_name = format!("{}ctx", first_letter);
-        let custom_ctx_type = format!("{}Ctx", first_letter.to_uppercase());
-        let template = include_str!("text/root_go");
-        let mut res = template.replace("##mod_name##", &self.mod_name);
-        res = res.replace("##ctx##", &custom_ctx_name);
-        res = res.replace("##Ctx##", &custom_ctx_type);
-        return res;
+    /// Whether the app reads anything from public/ at runtime: its pages,
+    /// the css tailwind builds into it or the vendored htmx.
+    fn uses_public_fs(&self) -> bool {
+        !self.config.templ || self.config.tailwind || self.vendors_htmx()
+    }
+
+    /// Whether htmx is served by the app itself. A vapp built before the
+    /// files were vendored (see `src/htmx/vendor.sh`) has placeholders
+    /// instead, and its projects load htmx from unpkg.
+    fn vendors_htmx(&self) -> bool {
+        self.config.htmx && self.scripts.iter().all(|s| s.is_vendored())
+    }
+
+    fn uses_session_store(&self, store: SessionStore) -> bool {
+        self.config.sessions && self.config.session_store == store
+    }
+
+    /// The directory and file name of the database the app uses locally.
+    fn local_db_file(&self) -> Option<(&str, String)> {
+        match self.config.database {
+            Database::None => None,
+            Database::Sqlite => Some(("data", format!("{}.db", self.config.app_name))),
+            Database::Turso => Some(("testdb", "testdb.db".to_string())),
+            Database::Postgres => None,
+        }
     }
 
-    fn get_custom_ctx_text_content(&self) -> String {
+    fn get_template_context(&self) -> Context {
         let first_letter = self.config.app_name.as_bytes()[0] as char;
-        let custom_ctx_name = format!("{}ctx", first_letter);
-        let custom_ctx_type = format!("{}Ctx", first_letter.to_uppercase());
-        let template = include_str!("text/custom_ctx_go");
-        let mut res = template.replace("##ctx##", &custom_ctx_name);
-        res = res.replace("##Ctx##", &custom_ctx_type);
-        let mut imports_replacement = if self.config.sessions {
-            "\"github.com/labstack/echo/v4\"\n\t\"github.com/gorilla/sessions\"".to_string()
-        } else {
-            "\"github.com/labstack/echo/v4\"".to_string()
+        let mut ctx = Context::new();
+        let mut set_str = |key: &str, value: String| ctx.insert(key.to_string(), Value::Str(value));
+        set_str("name", self.config.app_name.clone());
+        set_str("title", self.config.app_name.clone());
+        set_str("mod_name", self.mod_name.clone());
+        set_str("ctx", format!("{}ctx", first_letter));
+        set_str("Ctx", format!("{}Ctx", first_letter.to_uppercase()));
+        // echo's errors are used as is, chi and stdlib get a lookalike in the ctx package
+        match self.config.framework {
+            Framework::Echo => set_str("http_error", "echo.NewHTTPError".to_string()),
+            _ => set_str("http_error", format!("{}ctx.NewHTTPError", first_letter)),
         };
-        if self.config.turso {
-            imports_replacement += &format!("\n\t\"{}/internal/db\"", self.mod_name);
+        if let Some((dir, file)) = self.local_db_file() {
+            set_str("db_dir", dir.to_string());
+            set_str("db_file", format!("{}/{}", dir, file));
         }
-        res = res.replace("##imports##", &imports_replacement);
-        if self.config.sessions {
-            res = res.replace("##session_store##", "Store *sessions.CookieStore")
-        } else {
-            res = res.replace("##session_store##", "")
+        let flags = [
+            ("echo", self.config.framework == Framework::Echo),
+            ("chi", self.config.framework == Framework::Chi),
+            ("stdlib", self.config.framework == Framework::Stdlib),
+            ("templ", self.config.templ),
+            ("sessions", self.config.sessions),
+            (
+                "cookie_sessions",
+                self.uses_session_store(SessionStore::Cookie),
+            ),
+            (
+                "filesystem_sessions",
+                self.uses_session_store(SessionStore::Filesystem),
+            ),
+            ("db_sessions", self.uses_session_store(SessionStore::Db)),
+            (
+                "redis_sessions",
+                self.uses_session_store(SessionStore::Redis),
+            ),
+            ("db", self.config.database != Database::None),
+            ("sqlite", self.config.database == Database::Sqlite),
+            ("turso", self.config.database == Database::Turso),
+            ("postgres", self.config.database == Database::Postgres),
+            ("local_db", self.local_db_file().is_some()),
+            ("auth", self.config.auth),
+            ("htmx", self.config.htmx),
+            ("local_htmx", self.vendors_htmx()),
+            ("csrf", self.uses_csrf()),
+            ("public_fs", self.uses_public_fs()),
+            ("tailwind", self.config.tailwind),
+            ("air", self.config.air),
+        ];
+        for (key, value) in flags {
+            ctx.insert(key.to_string(), Value::Bool(value));
         }
-        if self.config.turso {
-            res = res.replace("##db##", "DB *db.DB")
-        } else {
-            res = res.replace("##db##", "")
+        // the first script is htmx itself, the rest are its extensions
+        let local = self.vendors_htmx();
+        let script_context = |script: &htmx::Script| {
+            let mut res = Context::new();
+            if local {
+                res.insert("src".to_string(), Value::Str(format!("/{}", script.path)));
+                res.insert("sri".to_string(), Value::Str(script.integrity()));
+            } else {
+                res.insert("src".to_string(), Value::Str(script.url.clone()));
+                res.insert("sri".to_string(), Value::Str(String::new()));
+            }
+            res
+        };
+        let mut scripts = self.scripts.iter().map(script_context);
+        let mut htmx_script = scripts.next().unwrap_or_default();
+        for key in ["src", "sri"] {
+            let value = htmx_script.remove(key).unwrap_or(Value::Str(String::new()));
+            ctx.insert(format!("htmx_{}", key), value);
         }
-        return res;
+        let extensions = self
+            .config
+            .htmx_extensions
+            .iter()
+            .zip(scripts)
+            .map(|(name, mut extension)| {
+                extension.insert("name".to_string(), Value::Str(name.clone()));
+                extension
+            })
+            .collect();
+        ctx.insert("htmx_extensions".to_string(), Value::List(extensions));
+        ctx.insert(
+            "htmx_ext_names".to_string(),
+            Value::Str(self.config.htmx_extensions.join(",")),
+        );
+        ctx.insert(
+            "session_secret".to_string(),
+            Value::Str("<your session secret>".to_string()),
+        );
+        let framework = self.config.framework;
+        let mut routes = vec![route(framework, "GET", "/", "RootGet")];
+        if self.config.auth {
+            routes.push(route(framework, "GET", "/register", "RegisterGet"));
+            routes.push(route(framework, "POST", "/register", "RegisterPost"));
+            routes.push(route(framework, "GET", "/login", "LoginGet"));
+            routes.push(route(framework, "POST", "/login", "LoginPost"));
+            routes.push(route(framework, "POST", "/logout", "LogoutPost"));
+        }
+        ctx.insert("routes".to_string(), Value::List(routes));
+        return ctx;
     }
 
-    fn get_env_text_content(&self) -> String {
-        let template = include_str!("text/env_go");
-        let mut res = template.to_string();
-
-        if self.config.sessions {
-            res += r#"func GetSessionSecret() string {
-    return os.Getenv("SESSION_SECRET")
-}"#;
+    fn get_steps(&self) -> Vec<Step> {
+        let mut steps = vec![Step::new(
+            "running go mod init",
+            "failed to run go mod init",
+            "go",
+            &["mod", "init", &self.mod_name],
+        )];
+        if self.config.tailwind {
+            steps.push(Step::new(
+                "running pnpm init",
+                "failed to run pnpm init",
+                "pnpm",
+                &["init"],
+            ));
+            steps.push(Step::new(
+                "installing tailwind",
+                "failed to install tailwind",
+                "pnpm",
+                &["add", "-D", "tailwindcss"],
+            ));
+            steps.push(Step::new(
+                "initializing tailwind",
+                "failed to initailize tailwind",
+                "npx",
+                &["tailwindcss", "init"],
+            ));
         }
-
-        if self.config.turso {
-            res += "\n\n";
-            res += r#"func GetDBUrl() string {
-    isProduction := os.Getenv("PRODUCTION")
-    if isProduction == "true" {
-        return os.Getenv("PROD_DB_URL")
-    } else {
-        return os.Getenv("DBURL")
-    }
-}"#;
+        if self.config.air && !self.config.templ {
+            steps.push(Step::new(
+                "initializing air",
+                "failed to initialize air",
+                "air",
+                &["init"],
+            ));
         }
-        return res;
+        if self.config.templ {
+            steps.push(
+                Step::new(
+                    "running templ generate",
+                    "failed to run templ generate, is templ installed?",
+                    "templ",
+                    &["generate"],
+                )
+                .rerun(),
+            );
+        }
+        steps.push(
+            Step::new(
+                "running go mod tidy",
+                "failed to run go mod tidy",
+                "go",
+                &["mod", "tidy"],
+            )
+            .rerun(),
+        );
+        steps.push(
+            Step::new(
+                "running go fmt",
+                "failed to run go fmt",
+                "go",
+                &["fmt", "./..."],
+            )
+            .rerun(),
+        );
+        return steps;
     }
+}
 
-    fn get_render_go_text_content(&self) -> String {
-        let res = include_str!("text/render_go").to_string();
-        return res;
+pub const MIGRATIONS_DIR: &str = "internal/db/migrations";
+
+/// The highest number a migration of the project at `root` starts with,
+/// 0 if it has none.
+pub fn last_migration_number(root: &str) -> u32 {
+    let mut last = 0;
+    if let Ok(entries) = std::fs::read_dir(format!("{}/{}", root, MIGRATIONS_DIR)) {
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let number = file_name
+                .to_string_lossy()
+                .split('_')
+                .next()
+                .unwrap_or("")
+                .to_string();
+            if let Ok(n) = number.parse::<u32>() {
+                last = last.max(n);
+            }
+        }
     }
+    return last;
+}
 
-    fn get_index_html_text_content(&self) -> String {
-        let template = include_str!("text/index_html");
-        let mut res = template.to_string();
-        res = res.replace("##name##", &self.config.app_name);
-        if self.config.tailwind {
-            res = res.replace(
-                "##css##",
-                "<link rel=\"stylesheet\" href=\"/css/index.css\">",
-            );
-            res = res.replace(
-                "##title##",
-                &format!("<h1 class=\"text-xl\">{}</h1>", self.config.app_name),
-            );
-        } else {
-            res = res.replace("##css##", "");
-            res = res.replace("##title##", &format!("<h1>{}</h1>", self.config.app_name));
-        }
-        if self.config.htmx {
-            res = res.replace(
-                "##htmx##",
-                "<script src=\"https://unpkg.com/htmx.org@1.9.10\"></script>",
-            );
-        } else {
-            res = res.replace("##htmx##", "");
+fn find_migration(root: &str, suffix: &str) -> Option<String> {
+    let entries = std::fs::read_dir(format!("{}/{}", root, MIGRATIONS_DIR)).ok()?;
+    entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .find(|name| name.ends_with(suffix))
+}
+
+fn route(framework: Framework, method: &str, path: &str, handler: &str) -> Context {
+    let mut route = Context::new();
+    route.insert(
+        "registration".to_string(),
+        Value::Str(registration(framework, method, path, handler)),
+    );
+    return route;
+}
+
+/// The line of `cmd/<app>/main.go` registering `routes.<handler>` for the
+/// upper case `method` and `path`.
+pub fn registration(framework: Framework, method: &str, path: &str, handler: &str) -> String {
+    match framework {
+        Framework::Echo => format!("e.{}(\"{}\", routes.{})", method, path, handler),
+        Framework::Chi => format!(
+            "r.{}{}(\"{}\", handle(routes.{}))",
+            &method[..1],
+            method[1..].to_lowercase(),
+            path,
+            handler
+        ),
+        Framework::Stdlib => {
+            // a plain "/" pattern would match every path
+            let path = if path == "/" { "/{$}" } else { path };
+            format!(
+                "mux.HandleFunc(\"{} {}\", handle(routes.{}))",
+                method, path, handler
+            )
         }
-        return res;
     }
+}
 
-    fn get_db_go_text_content(&self) -> String {
-        let res = include_str!("text/db_go").to_string();
-        return res;
+/// An external command that `AppBuilder::build` runs inside the project.
+#[derive(Debug)]
+pub struct Step {
+    message: &'static str,
+    error: &'static str,
+    program: &'static str,
+    args: Vec<String>,
+    /// Whether the step has to run again whenever files of an existing
+    /// project change, rather than only when the feature needing it is new.
+    pub rerun: bool,
+}
+
+impl Step {
+    fn new(
+        message: &'static str,
+        error: &'static str,
+        program: &'static str,
+        args: &[&str],
+    ) -> Self {
+        Self {
+            message,
+            error,
+            program,
+            args: args.iter().map(|a| a.to_string()).collect(),
+            rerun: false,
+        }
     }
 
-    fn get_css_text_content(&self) -> String {
-        let res = include_str!("text/index_css").to_string();
-        return res;
+    fn rerun(mut self) -> Self {
+        self.rerun = true;
+        self
     }
 
-    fn get_cmd_main_go_text_content(&self) -> String {
-        let mut res: String;
-        let first_letter = self.config.app_name.as_bytes()[0] as char;
-        let custom_ctx_name = format!("{}ctx", first_letter);
-        if self.config.turso && self.config.sessions && self.config.tailwind {
-            res = include_str!("text/cmd_main_go_full").to_string();
-        } else if self.config.turso && self.config.sessions {
-            res = include_str!("text/cmd_main_go_turso_session").to_string();
-        } else if self.config.turso && self.config.tailwind {
-            res = include_str!("text/cmd_main_go_turso_tailwind").to_string();
-        } else if self.config.turso {
-            res = include_str!("text/cmd_main_go_turso").to_string();
-        } else if self.config.sessions && self.config.tailwind {
-            res = include_str!("text/cmd_main_go_session_tailwind").to_string();
-        } else if self.config.sessions {
-            res = include_str!("text/cmd_main_go_session").to_string();
-        } else if self.config.tailwind {
-            res = include_str!("text/cmd_main_go_tailwind").to_string();
-        } else {
-            res = include_str!("text/cmd_main_go").to_string();
+    pub fn command_line(&self) -> String {
+        let mut res = self.program.to_string();
+        for arg in &self.args {
+            res += " ";
+            res += arg;
         }
-        res = res.replace("##mod_name##", &self.mod_name);
-        res = res.replace("##ctx##", &custom_ctx_name);
         return res;
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanFormat {
+    Text,
+    Json,
+}
+
 #[derive(Debug)]
 pub struct AppBuilder {
     config: AppBuilderConfig,
 }
 
 impl AppBuilder {
-    pub fn new(config: Config) -> Result<Self, AppBuilderError> {
-        let conf = AppBuilderConfig::new(config)?;
+    pub fn new(config: Config, templates: &Templates) -> Result<Self, AppBuilderError> {
+        let conf = AppBuilderConfig::new(config, templates)?;
         return Ok(Self { config: conf });
     }
 
-    pub fn build(&self) -> Result<(), AppBuilderError> {
-        self.create_dirs()?;
-        self.create_files()?;
-        self.run_go_mod_init()?;
-        self.run_pnpm_init()?;
-        self.install_tailwind()?;
-        self.initialize_tailwind()?;
-        self.initialize_air()?;
-        self.run_go_mod_tidy()?;
-        self.run_go_fmt()?;
-        println!("done");
-        Ok(())
+    /// Works out what `config` would generate for the existing project at
+    /// `path_to_project`, for updating it in place instead of building it.
+    pub fn for_project(
+        config: Config,
+        path_to_project: &str,
+        templates: &Templates,
+    ) -> Result<Self, AppBuilderError> {
+        let conf = AppBuilderConfig::at(config, path_to_project.to_string(), templates)?;
+        return Ok(Self { config: conf });
     }
 
-    fn run_go_mod_init(&self) -> Result<(), AppBuilderError> {
-        println!("running go mod init");
-        let mut cmd = Command::new("go");
-        cmd.arg("mod")
-            .arg("init")
-            .arg(&self.config.mod_name)
-            .current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
-        let exit_code = get_exit_code(Ok(output.status));
-        if exit_code != 0 {
-            return Err("failed to run go mod init".into());
-        }
-        Ok(())
+    pub fn path_to_project(&self) -> &str {
+        &self.config.path_to_project
+    }
+
+    pub fn dirs(&self) -> &[String] {
+        &self.config.dirs_to_create
+    }
+
+    pub fn files(&self) -> &BTreeMap<String, String> {
+        &self.config.file_to_text_map
     }
 
-    fn run_pnpm_init(&self) -> Result<(), AppBuilderError> {
-        if !self.config.config.tailwind {
-            return Ok(());
+    pub fn steps(&self) -> Vec<Step> {
+        self.config.get_steps()
+    }
+
+    /// The values the project's templates are rendered with, for rendering
+    /// further templates into it.
+    pub fn template_context(&self) -> Context {
+        self.config.get_template_context()
+    }
+
+    /// Builds the project in a staging directory next to `path_to_project`
+    /// and only moves it into place once every step has succeeded, so a
+    /// failed build never leaves a half-created project behind. With
+    /// `keep_on_failure` the staging directory is left for debugging.
+    pub fn build(&self, keep_on_failure: bool) -> Result<(), AppBuilderError> {
+        let project = &self.config.path_to_project;
+        let staging = &self.config.path_to_staging;
+        if Path::new(project).exists() {
+            return Err(format!("{} already exists", project).into());
         }
-        println!("running pnpm init");
-        let mut cmd = Command::new("pnpm");
-        cmd.arg("init").current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
-        let exit_code = get_exit_code(Ok(output.status));
-        if exit_code != 0 {
-            return Err("failed to run pnpm init".into());
+        if Path::new(staging).exists() {
+            return Err(format!(
+                "{} already exists, remove it (left over from an earlier failed build?)",
+                staging
+            )
+            .into());
+        }
+        let res = self.build_in(staging).and_then(|_| {
+            std::fs::rename(staging, project)
+                .map_err(|e| format!("failed to move {} to {}: {}", staging, project, e).into())
+        });
+        match res {
+            Ok(_) => {
+                println!("done");
+                Ok(())
+            }
+            Err(e) => {
+                if keep_on_failure {
+                    eprintln!("keeping the partially built project at {}", staging);
+                } else if Path::new(staging).exists() {
+                    std::fs::remove_dir_all(staging)?;
+                }
+                Err(e)
+            }
         }
-        Ok(())
     }
 
-    fn install_tailwind(&self) -> Result<(), AppBuilderError> {
-        if !self.config.config.tailwind {
-            return Ok(());
-        }
-        println!("installing tailwind");
-        let mut cmd = Command::new("pnpm");
-        cmd.arg("add")
-            .arg("-D")
-            .arg("tailwindcss")
-            .current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
-        let exit_code = get_exit_code(Ok(output.status));
-        if exit_code != 0 {
-            return Err("failed to install tailwind".into());
+    /// Writes the generated files into `root` and formats them the way
+    /// `go fmt` would, without running any of the other steps.
+    pub fn generate_into(&self, root: &str) -> Result<(), AppBuilderError> {
+        self.create_dirs(root)?;
+        self.create_files(root)?;
+        let mut cmd = Command::new("gofmt");
+        cmd.arg("-w").arg(".").current_dir(root);
+        if cmd.output().is_err() {
+            eprintln!("gofmt not found, comparing unformatted go files");
         }
         Ok(())
     }
 
-    fn initialize_tailwind(&self) -> Result<(), AppBuilderError> {
-        if !self.config.config.tailwind {
-            return Ok(());
-        }
-        println!("initializing tailwind");
-        let mut cmd = Command::new("npx");
-        cmd.arg("tailwindcss")
-            .arg("init")
-            .current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
-        let exit_code = get_exit_code(Ok(output.status));
-        if exit_code != 0 {
-            return Err("failed to initailize tailwind".into());
+    fn build_in(&self, root: &str) -> Result<(), AppBuilderError> {
+        self.create_dirs(root)?;
+        self.create_files(root)?;
+        for step in self.config.get_steps() {
+            self.run_step(root, &step)?;
         }
+        self.manifest(root).write(root)?;
         Ok(())
     }
 
-    fn initialize_air(&self) -> Result<(), AppBuilderError> {
-        if !self.config.config.air {
-            return Ok(());
+    /// Records the generated files as they are on disk in `root`, i.e.
+    /// after `go fmt` and friends have run.
+    pub fn manifest(&self, root: &str) -> Manifest {
+        let mut config = self.config.config.clone();
+        config.module_path = Some(self.config.mod_name.clone());
+        let mut manifest = Manifest::new(config, self.config.mod_name.clone());
+        for (file, content) in &self.config.file_to_text_map {
+            match std::fs::read_to_string(format!("{}/{}", root, file)) {
+                Ok(on_disk) => manifest.record(file, &on_disk),
+                Err(_) => manifest.record(file, content),
+            }
         }
-        println!("initializing air");
-        let mut cmd = Command::new("air");
-        cmd.arg("init").current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
-        let exit_code = get_exit_code(Ok(output.status));
-        if exit_code != 0 {
-            return Err("failed to initialize air".into());
+        return manifest;
+    }
+
+    /// Describes everything `build` would do without touching the disk.
+    pub fn plan(&self, format: PlanFormat) -> String {
+        match format {
+            PlanFormat::Text => self.plan_text(),
+            PlanFormat::Json => self.plan_json(),
         }
-        Ok(())
     }
 
-    fn run_go_mod_tidy(&self) -> Result<(), AppBuilderError> {
-        println!("running go mod tidy");
-        let mut cmd = Command::new("go");
-        cmd.arg("mod")
-            .arg("tidy")
-            .current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
-        let exit_code = get_exit_code(Ok(output.status));
-        if exit_code != 0 {
-            return Err("failed to run go mod tidy".into());
+    /// The dirs and files `build` creates, with the size of each file,
+    /// including the manifest and its base copies.
+    fn planned(&self) -> (Vec<String>, Vec<(String, usize)>) {
+        let mut dirs = self.config.dirs_to_create.clone();
+        let mut files: Vec<(String, usize)> = self
+            .config
+            .file_to_text_map
+            .iter()
+            .map(|(file, content)| (file.clone(), content.len()))
+            .collect();
+        let manifest = self.manifest(&self.config.path_to_project);
+        for (file, content) in manifest.contents() {
+            let depth = file.matches('/').count();
+            for n in (1..=depth).rev() {
+                let dir = nth_ancestor(&file, n).to_string();
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+            files.push((file, content.len()));
         }
-        Ok(())
+        return (dirs, files);
     }
 
-    fn run_go_fmt(&self) -> Result<(), AppBuilderError> {
-        println!("running go fmt");
-        let mut cmd = Command::new("go");
-        cmd.arg("fmt")
-            .arg("./...")
-            .current_dir(&self.config.path_to_project);
-        let output = cmd.output()?;
+    fn plan_text(&self) -> String {
+        let (dirs, files) = self.planned();
+        let mut entries: Vec<(&str, Option<usize>)> =
+            dirs.iter().map(|dir| (dir.as_str(), None)).collect();
+        for (file, size) in &files {
+            entries.push((file, Some(*size)));
+        }
+        entries.sort_by(|a, b| a.0.split('/').cmp(b.0.split('/')));
+
+        let mut res = format!("{}/\n", self.config.path_to_project);
+        for (i, (path, size)) in entries.iter().enumerate() {
+            let depth = path.matches('/').count();
+            let mut prefix = String::new();
+            for level in 0..depth {
+                let ancestor = nth_ancestor(path, depth - level);
+                if is_last_child(&entries[i + 1..], nth_ancestor(ancestor, 1)) {
+                    prefix += "    ";
+                } else {
+                    prefix += "│   ";
+                }
+            }
+            let parent = nth_ancestor(path, 1);
+            let connector = if is_last_child(&entries[i + 1..], parent) {
+                "└── "
+            } else {
+                "├── "
+            };
+            let name = path.rsplit('/').next().unwrap_or(path);
+            match size {
+                Some(size) => res += &format!("{}{}{} ({} B)\n", prefix, connector, name, size),
+                None => res += &format!("{}{}{}/\n", prefix, connector, name),
+            }
+        }
+
+        res += "\ncommands:\n";
+        for step in self.config.get_steps() {
+            res += &format!("    $ {}\n", step.command_line());
+        }
+        return res;
+    }
+
+    fn plan_json(&self) -> String {
+        let (dirs, files) = self.planned();
+        let dirs = dirs.into_iter().map(Json::Str).collect();
+        let files = files
+            .into_iter()
+            .map(|(file, size)| {
+                Json::Object(vec![
+                    ("path".to_string(), Json::Str(file)),
+                    ("size".to_string(), Json::Int(size as i64)),
+                ])
+            })
+            .collect();
+        let commands = self
+            .config
+            .get_steps()
+            .into_iter()
+            .map(|step| {
+                Json::Object(vec![
+                    ("program".to_string(), Json::Str(step.program.to_string())),
+                    (
+                        "args".to_string(),
+                        Json::Array(step.args.into_iter().map(Json::Str).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        let plan = Json::Object(vec![
+            (
+                "path_to_project".to_string(),
+                Json::Str(self.config.path_to_project.clone()),
+            ),
+            (
+                "mod_name".to_string(),
+                Json::Str(self.config.mod_name.clone()),
+            ),
+            ("dirs".to_string(), Json::Array(dirs)),
+            ("files".to_string(), Json::Array(files)),
+            ("commands".to_string(), Json::Array(commands)),
+        ]);
+        return plan.to_string_pretty() + "\n";
+    }
+
+    pub fn run_step(&self, root: &str, step: &Step) -> Result<(), AppBuilderError> {
+        println!("{}", step.message);
+        let mut cmd = Command::new(step.program);
+        cmd.args(&step.args).current_dir(root);
+        let output = cmd
+            .output()
+            .map_err(|e| format!("{}: could not run {}: {}", step.error, step.program, e))?;
         let exit_code = get_exit_code(Ok(output.status));
         if exit_code != 0 {
-            return Err("failed to run go fmt".into());
+            return Err(step.error.into());
         }
         Ok(())
     }
 
-    fn create_dirs(&self) -> Result<(), AppBuilderError> {
+    fn create_dirs(&self, root: &str) -> Result<(), AppBuilderError> {
+        std::fs::create_dir(root)?;
         for dir in &self.config.dirs_to_create {
-            std::fs::create_dir(dir)?;
+            std::fs::create_dir(format!("{}/{}", root, dir))?;
         }
         Ok(())
     }
 
-    fn create_files(&self) -> Result<(), AppBuilderError> {
+    fn create_files(&self, root: &str) -> Result<(), AppBuilderError> {
         for (file, content) in &self.config.file_to_text_map {
-            let mut file = std::fs::File::create(file)?;
-            file.write(content.as_bytes())?;
+            let path = format!("{}/{}", root, file);
+            let mut file = std::fs::File::create(path)?;
+            file.write_all(content.as_bytes())?;
         }
         Ok(())
     }
 }
+
+/// Returns `path` with its last `n` components removed, "" being the root.
+fn nth_ancestor(path: &str, n: usize) -> &str {
+    let mut res = path;
+    for _ in 0..n {
+        res = match res.rfind('/') {
+            Some(i) => &res[..i],
+            None => "",
+        };
+    }
+    return res;
+}
+
+/// Whether no entry after the current one shares the directory `parent`,
+/// i.e. the current subtree of `parent` is the last one to be printed.
+fn is_last_child(rest: &[(&str, Option<usize>)], parent: &str) -> bool {
+    !rest.iter().any(|(path, _)| nth_ancestor(path, 1) == parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+
+    #[test]
+    fn plan_lists_the_manifest() {
+        let config = ConfigBuilder::new()
+            .add_app_name("vapp_plan_test".to_string())
+            .add_module_path("example.com/app".to_string())
+            .out();
+        let builder = AppBuilder::new(config, &Templates::default()).unwrap();
+        let json = builder.plan(PlanFormat::Json);
+        for file in [".vapp/manifest.json", ".vapp/base/main.go"] {
+            assert!(json.contains(&format!("\"{}\"", file)), "{}", file);
+        }
+        assert!(!json.contains(".vapp/base/.env\""));
+        let text = builder.plan(PlanFormat::Text);
+        assert!(text.contains("├── .vapp/\n"), "{}", text);
+        assert!(text.contains("└── manifest.json ("), "{}", text);
+    }
+}
@@ -0,0 +1,540 @@
+use std::error::Error;
+
+use crate::{
+    app_builder::PlanFormat,
+    config::{ConfigBuilder, Database, Feature, Framework, SessionStore},
+    generate::{Field, METHODS},
+    htmx,
+};
+
+type CliError = Box<dyn Error>;
+
+pub const USAGE: &str = "usage: vapp [new] [<app-name>] [options]
+       vapp add <feature> [--templates <dir>]
+       vapp upgrade [--templates <dir>]
+       vapp generate route <name> [--methods get,post] [--templates <dir>]
+       vapp generate resource <name> <field:type>... [--templates <dir>]
+       vapp generate migration <name> [--templates <dir>]
+       vapp templates export <dir>
+
+commands:
+    new                          create a new project (the default)
+    add <feature>                add sessions, sqlite, turso, postgres, auth, htmx,
+                                 tailwind or air to the project in the current directory
+    upgrade                      merge template changes from this vapp into the
+                                 project in the current directory
+    generate route <name>        add the route /<name> with its handlers and page;
+                                 --methods picks them from get, post, put, patch
+                                 and delete (default get)
+    generate resource <name>     add a table with list/show/new/edit pages for it
+                                 (needs a database); fields are given as name:type with
+                                 type string, text, int, float or bool
+    generate migration <name>    add the next numbered sql migration (needs a database)
+    templates export <dir>       write the built-in templates to <dir> for overriding
+
+options:
+    --module <path>              go module path (default github.com/<git user>/<app-name>)
+    --framework <name>           route with echo (the default), chi or stdlib
+                                 (net/http with go 1.22 method patterns)
+    --templ, --no-templ          render templ components instead of html/template pages
+    --sessions, --no-sessions    use gorilla sessions
+    --session-store <name>       keep sessions in a cookie (the default), on the
+                                 filesystem, in the db or in redis; implies --sessions
+    --database <name>            sqlite (a local file), turso (libsql, with a local
+                                 file in development), postgres (with a docker
+                                 compose service for development) or none
+    --turso, --no-turso          same as --database turso and --database none
+    --auth, --no-auth            add register/login/logout (needs sessions and a database)
+    --htmx, --no-htmx            use htmx
+    --htmx-version <version>     vendor htmx 2.0.4 (the default) or 1.9.12; implies --htmx
+    --htmx-ext <names>           also vendor the htmx extensions sse, ws and json-enc,
+                                 given comma separated; implies --htmx
+    --tailwind, --no-tailwind    use tailwind
+    --air, --no-air              use air
+    --answers <file>             read answers from a vapp.toml answers file
+    --save-answers <file>        write the final answers to a vapp.toml answers file
+                                 once they are known to build (not with --dry-run)
+    --dry-run                    print what would be generated without touching the disk
+    --format <text|json>         output format of --dry-run (default text)
+    --templates <dir>            look for templates in <dir> before ~/.config/vapp/templates
+    --keep-on-failure            keep the partially built project if a step fails
+    -y, --yes                    accept the defaults for anything not given
+    -h, --help                   print this message";
+
+#[derive(Debug)]
+pub enum Command {
+    New(NewArgs),
+    Add {
+        feature: Feature,
+        templates: Option<String>,
+    },
+    Upgrade {
+        templates: Option<String>,
+    },
+    GenerateRoute {
+        name: String,
+        methods: Vec<String>,
+        templates: Option<String>,
+    },
+    GenerateResource {
+        name: String,
+        fields: Vec<Field>,
+        templates: Option<String>,
+    },
+    GenerateMigration {
+        name: String,
+        templates: Option<String>,
+    },
+    ExportTemplates(String),
+    Help,
+}
+
+#[derive(Debug)]
+pub struct NewArgs {
+    pub builder: ConfigBuilder,
+    pub answers: Option<String>,
+    pub save_answers: Option<String>,
+    pub dry_run: bool,
+    pub format: PlanFormat,
+    pub templates: Option<String>,
+    pub keep_on_failure: bool,
+    pub yes: bool,
+}
+
+pub fn parse_args<I>(args: I) -> Result<Command, CliError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    match args.peek().map(|a| a.as_str()) {
+        Some("new") => {
+            args.next();
+        }
+        Some("add") => {
+            args.next();
+            return parse_add_args(args);
+        }
+        Some("upgrade") => {
+            args.next();
+            return parse_upgrade_args(args);
+        }
+        Some("generate") => {
+            args.next();
+            return parse_generate_args(args);
+        }
+        Some("templates") => {
+            args.next();
+            return parse_templates_args(args);
+        }
+        _ => (),
+    }
+    let mut builder = ConfigBuilder::new();
+    let mut answers = None;
+    let mut save_answers = None;
+    let mut dry_run = false;
+    let mut format = PlanFormat::Text;
+    let mut templates = None;
+    let mut keep_on_failure = false;
+    let mut yes = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--module" => builder = builder.add_module_path(value_for(&arg, args.next())?),
+            "--answers" => answers = Some(value_for(&arg, args.next())?),
+            "--save-answers" => save_answers = Some(value_for(&arg, args.next())?),
+            "--dry-run" => dry_run = true,
+            "--format" => {
+                format = match value_for(&arg, args.next())?.as_str() {
+                    "text" => PlanFormat::Text,
+                    "json" => PlanFormat::Json,
+                    other => {
+                        return Err(
+                            format!("unknown format {}, expected text or json", other).into()
+                        )
+                    }
+                }
+            }
+            "--templates" => templates = Some(value_for(&arg, args.next())?),
+            "--keep-on-failure" => keep_on_failure = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-y" | "--yes" => yes = true,
+            "--framework" => {
+                let name = value_for(&arg, args.next())?;
+                builder = match Framework::from_name(&name) {
+                    Some(framework) => builder.add_framework(framework),
+                    None => {
+                        return Err(format!(
+                            "unknown framework {}, expected echo, chi or stdlib",
+                            name
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--templ" => builder = builder.add_templ(true),
+            "--no-templ" => builder = builder.add_templ(false),
+            "--sessions" => builder = builder.add_sessions(true),
+            "--no-sessions" => builder = builder.add_sessions(false),
+            "--session-store" => {
+                let name = value_for(&arg, args.next())?;
+                builder = match SessionStore::from_name(&name) {
+                    Some(store) => builder.add_sessions(true).add_session_store(store),
+                    None => {
+                        return Err(format!(
+                            "unknown session store {}, expected cookie, filesystem, db or redis",
+                            name
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--database" => {
+                let name = value_for(&arg, args.next())?;
+                builder = match Database::from_name(&name) {
+                    Some(database) => builder.add_database(database),
+                    None => {
+                        return Err(format!(
+                            "unknown database {}, expected sqlite, turso, postgres or none",
+                            name
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--turso" => builder = builder.add_database(Database::Turso),
+            "--no-turso" => builder = builder.add_database(Database::None),
+            "--auth" => builder = builder.add_auth(true),
+            "--no-auth" => builder = builder.add_auth(false),
+            "--htmx" => builder = builder.add_htmx(true),
+            "--no-htmx" => builder = builder.add_htmx(false),
+            "--htmx-version" => {
+                let version = value_for(&arg, args.next())?;
+                if !htmx::VERSIONS.contains(&version.as_str()) {
+                    return Err(format!(
+                        "unknown htmx version {}, expected one of {}",
+                        version,
+                        htmx::VERSIONS.join(", ")
+                    )
+                    .into());
+                }
+                builder = builder.add_htmx(true).add_htmx_version(version);
+            }
+            "--htmx-ext" => {
+                let mut extensions = Vec::new();
+                for name in value_for(&arg, args.next())?.split(',') {
+                    let name = name.trim().to_string();
+                    if !htmx::EXTENSIONS.contains(&name.as_str()) {
+                        return Err(format!(
+                            "unknown htmx extension {}, expected {}",
+                            name,
+                            htmx::EXTENSIONS.join(", ")
+                        )
+                        .into());
+                    }
+                    if !extensions.contains(&name) {
+                        extensions.push(name);
+                    }
+                }
+                builder = builder.add_htmx(true).add_htmx_extensions(extensions);
+            }
+            "--tailwind" => builder = builder.add_tailwind(true),
+            "--no-tailwind" => builder = builder.add_tailwind(false),
+            "--air" => builder = builder.add_air(true),
+            "--no-air" => builder = builder.add_air(false),
+            flag if flag.starts_with('-') => {
+                return Err(format!("unknown option {}\n\n{}", flag, USAGE).into());
+            }
+            name => {
+                if builder.app_name().is_some() {
+                    return Err(format!("unexpected argument {}\n\n{}", name, USAGE).into());
+                }
+                builder = builder.add_app_name(name.to_string());
+            }
+        }
+    }
+    return Ok(Command::New(NewArgs {
+        builder,
+        answers,
+        save_answers,
+        dry_run,
+        format,
+        templates,
+        keep_on_failure,
+        yes,
+    }));
+}
+
+fn parse_add_args<I>(mut args: I) -> Result<Command, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut feature = None;
+    let mut templates = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--templates" => templates = Some(value_for(&arg, args.next())?),
+            name if feature.is_none() && !name.starts_with('-') => {
+                feature = match Feature::from_name(name) {
+                    Some(f) => Some(f),
+                    None => return Err(format!("unknown feature {}", name).into()),
+                };
+            }
+            other => return Err(format!("unexpected argument {}\n\n{}", other, USAGE).into()),
+        }
+    }
+    match feature {
+        Some(feature) => Ok(Command::Add { feature, templates }),
+        None => Err(format!("expected `vapp add <feature>`\n\n{}", USAGE).into()),
+    }
+}
+
+fn parse_upgrade_args<I>(mut args: I) -> Result<Command, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut templates = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--templates" => templates = Some(value_for(&arg, args.next())?),
+            other => return Err(format!("unexpected argument {}\n\n{}", other, USAGE).into()),
+        }
+    }
+    Ok(Command::Upgrade { templates })
+}
+
+fn parse_generate_args<I>(mut args: I) -> Result<Command, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    match args.next().as_deref() {
+        Some("route") => (),
+        Some("resource") => return parse_generate_resource_args(args),
+        Some("migration") => return parse_generate_migration_args(args),
+        Some("-h" | "--help") => return Ok(Command::Help),
+        _ => {
+            return Err(format!(
+                "expected `vapp generate route|resource|migration <name>`\n\n{}",
+                USAGE
+            )
+            .into())
+        }
+    }
+    let mut name = None;
+    let mut methods = vec!["get".to_string()];
+    let mut templates = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--templates" => templates = Some(value_for(&arg, args.next())?),
+            "--methods" => {
+                methods = Vec::new();
+                for method in value_for(&arg, args.next())?.split(',') {
+                    let method = method.trim().to_lowercase();
+                    if !METHODS.contains(&method.as_str()) {
+                        return Err(format!(
+                            "unknown method {}, expected one of {}",
+                            method,
+                            METHODS.join(", ")
+                        )
+                        .into());
+                    }
+                    if !methods.contains(&method) {
+                        methods.push(method);
+                    }
+                }
+            }
+            n if name.is_none() && !n.starts_with('-') => name = Some(n.to_string()),
+            other => return Err(format!("unexpected argument {}\n\n{}", other, USAGE).into()),
+        }
+    }
+    match name {
+        Some(name) => Ok(Command::GenerateRoute {
+            name,
+            methods,
+            templates,
+        }),
+        None => Err(format!("expected `vapp generate route <name>`\n\n{}", USAGE).into()),
+    }
+}
+
+fn parse_generate_resource_args<I>(mut args: I) -> Result<Command, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut name = None;
+    let mut fields = Vec::new();
+    let mut templates = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--templates" => templates = Some(value_for(&arg, args.next())?),
+            other if other.starts_with('-') => {
+                return Err(format!("unexpected argument {}\n\n{}", other, USAGE).into())
+            }
+            n if name.is_none() => name = Some(n.to_string()),
+            spec => {
+                let field = Field::parse(spec)?;
+                if fields.iter().any(|f: &Field| f.name == field.name) {
+                    return Err(format!("field {} is given more than once", field.name).into());
+                }
+                fields.push(field);
+            }
+        }
+    }
+    match name {
+        Some(name) => Ok(Command::GenerateResource {
+            name,
+            fields,
+            templates,
+        }),
+        None => Err(format!(
+            "expected `vapp generate resource <name> <field:type>...`\n\n{}",
+            USAGE
+        )
+        .into()),
+    }
+}
+
+fn parse_generate_migration_args<I>(mut args: I) -> Result<Command, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut name = None;
+    let mut templates = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--templates" => templates = Some(value_for(&arg, args.next())?),
+            n if name.is_none() && !n.starts_with('-') => name = Some(n.to_string()),
+            other => return Err(format!("unexpected argument {}\n\n{}", other, USAGE).into()),
+        }
+    }
+    match name {
+        Some(name) => Ok(Command::GenerateMigration { name, templates }),
+        None => Err(format!("expected `vapp generate migration <name>`\n\n{}", USAGE).into()),
+    }
+}
+
+fn parse_templates_args<I>(mut args: I) -> Result<Command, CliError>
+where
+    I: Iterator<Item = String>,
+{
+    match (args.next().as_deref(), args.next(), args.next()) {
+        (Some("export"), Some(dir), None) if !dir.starts_with('-') => {
+            Ok(Command::ExportTemplates(dir))
+        }
+        (Some("-h" | "--help"), _, _) => Ok(Command::Help),
+        _ => Err(format!("expected `vapp templates export <dir>`\n\n{}", USAGE).into()),
+    }
+}
+
+fn value_for(flag: &str, value: Option<String>) -> Result<String, CliError> {
+    match value {
+        Some(v) if !v.starts_with('-') => Ok(v),
+        _ => Err(format!("{} expects a value\n\n{}", flag, USAGE).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Command, CliError> {
+        return parse_args(line.split_whitespace().map(|a| a.to_string()));
+    }
+
+    fn parse_new(line: &str) -> NewArgs {
+        match parse(line).unwrap() {
+            Command::New(args) => args,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn error(line: &str) -> String {
+        let err = parse(line).unwrap_err().to_string();
+        // the usage follows every error
+        return err.split("\n\n").next().unwrap_or_default().to_string();
+    }
+
+    #[test]
+    fn parses_new_flags() {
+        let args = parse_new(
+            "new app --framework chi --templ --database postgres --session-store redis --htmx-ext sse,ws,sse --no-air --dry-run --format json",
+        );
+        let builder = &args.builder;
+        assert_eq!(builder.app_name(), Some("app"));
+        assert_eq!(builder.framework(), Some(Framework::Chi));
+        assert_eq!(builder.templ(), Some(true));
+        assert_eq!(builder.database(), Some(Database::Postgres));
+        assert_eq!(builder.sessions(), Some(true));
+        assert_eq!(builder.session_store(), Some(SessionStore::Redis));
+        assert_eq!(builder.htmx(), Some(true));
+        assert_eq!(builder.air(), Some(false));
+        assert_eq!(builder.auth(), None);
+        assert!(args.dry_run);
+        assert_eq!(args.format, PlanFormat::Json);
+        assert!(!args.yes);
+    }
+
+    #[test]
+    fn new_is_the_default_command() {
+        let args = parse_new("app -y");
+        assert_eq!(args.builder.app_name(), Some("app"));
+        assert!(args.yes);
+        assert!(parse_new("--yes app").yes);
+        assert_eq!(parse_new("--yes").builder.app_name(), None);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(error("app --frobnicate"), "unknown option --frobnicate");
+        assert_eq!(error("app other"), "unexpected argument other");
+        assert_eq!(error("app --framework"), "--framework expects a value");
+        assert_eq!(error("app --module --yes"), "--module expects a value");
+        assert_eq!(
+            error("app --framework gin"),
+            "unknown framework gin, expected echo, chi or stdlib"
+        );
+        assert_eq!(
+            error("app --htmx-ext sse,nope"),
+            "unknown htmx extension nope, expected sse, ws, json-enc"
+        );
+        assert_eq!(error("add"), "expected `vapp add <feature>`");
+    }
+
+    #[test]
+    fn parses_subcommands() {
+        match parse("add auth --templates dir").unwrap() {
+            Command::Add { feature, templates } => {
+                assert_eq!(feature.name(), "auth");
+                assert_eq!(templates.as_deref(), Some("dir"));
+            }
+            other => panic!("{:?}", other),
+        }
+        match parse("generate route about --methods get,post").unwrap() {
+            Command::GenerateRoute { name, methods, .. } => {
+                assert_eq!(name, "about");
+                assert_eq!(methods, ["get", "post"]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(parse("upgrade").unwrap(), Command::Upgrade { .. }));
+        assert!(matches!(parse("--help").unwrap(), Command::Help));
+        assert!(matches!(parse("generate route -h").unwrap(), Command::Help));
+    }
+
+    #[test]
+    fn parses_resource_fields() {
+        match parse("generate resource post title:string body:text").unwrap() {
+            Command::GenerateResource { name, fields, .. } => {
+                assert_eq!(name, "post");
+                let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+                assert_eq!(names, ["title", "body"]);
+            }
+            other => panic!("{:?}", other),
+        }
+        let err = parse("generate resource post title:string title:text").unwrap_err();
+        assert_eq!(err.to_string(), "field title is given more than once");
+    }
+}
@@ -1,19 +1,39 @@
+use crate::htmx;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub app_name: String,
+    pub module_path: Option<String>,
+    pub framework: Framework,
+    /// Render a-h/templ components instead of html/template pages.
+    pub templ: bool,
     pub sessions: bool,
-    pub turso: bool,
+    /// Where sessions are kept, when `sessions` is on.
+    pub session_store: SessionStore,
+    pub database: Database,
+    pub auth: bool,
     pub htmx: bool,
+    /// The vendored htmx version, one of `htmx::VERSIONS`.
+    pub htmx_version: String,
+    /// htmx extensions vendored next to it, from `htmx::EXTENSIONS`.
+    pub htmx_extensions: Vec<String>,
     pub tailwind: bool,
     pub air: bool,
 }
 
+#[derive(Debug, Default)]
 pub struct ConfigBuilder {
     app_name: Option<String>,
+    module_path: Option<String>,
+    framework: Option<Framework>,
+    templ: Option<bool>,
     sessions: Option<bool>,
-    turso: Option<bool>,
+    session_store: Option<SessionStore>,
+    database: Option<Database>,
+    auth: Option<bool>,
     htmx: Option<bool>,
+    htmx_version: Option<String>,
+    htmx_extensions: Option<Vec<String>>,
     tailwind: Option<bool>,
     air: Option<bool>,
 }
@@ -22,9 +42,16 @@ impl ConfigBuilder {
     pub fn new() -> Self {
         ConfigBuilder {
             app_name: None,
+            module_path: None,
+            framework: None,
+            templ: None,
             sessions: None,
-            turso: None,
+            session_store: None,
+            database: None,
+            auth: None,
             htmx: None,
+            htmx_version: None,
+            htmx_extensions: None,
             tailwind: None,
             air: None,
         }
@@ -35,13 +62,38 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn add_module_path(mut self, module_path: String) -> Self {
+        self.module_path = Some(module_path);
+        self
+    }
+
+    pub fn add_framework(mut self, value: Framework) -> Self {
+        self.framework = Some(value);
+        self
+    }
+
+    pub fn add_templ(mut self, value: bool) -> Self {
+        self.templ = Some(value);
+        self
+    }
+
     pub fn add_sessions(mut self, value: bool) -> Self {
         self.sessions = Some(value);
         self
     }
 
-    pub fn add_turso(mut self, value: bool) -> Self {
-        self.turso = Some(value);
+    pub fn add_session_store(mut self, value: SessionStore) -> Self {
+        self.session_store = Some(value);
+        self
+    }
+
+    pub fn add_database(mut self, value: Database) -> Self {
+        self.database = Some(value);
+        self
+    }
+
+    pub fn add_auth(mut self, value: bool) -> Self {
+        self.auth = Some(value);
         self
     }
 
@@ -50,6 +102,16 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn add_htmx_version(mut self, value: String) -> Self {
+        self.htmx_version = Some(value);
+        self
+    }
+
+    pub fn add_htmx_extensions(mut self, value: Vec<String>) -> Self {
+        self.htmx_extensions = Some(value);
+        self
+    }
+
     pub fn add_tailwind(mut self, value: bool) -> Self {
         self.tailwind = Some(value);
         self
@@ -60,32 +122,248 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn app_name(&self) -> Option<&str> {
+        self.app_name.as_deref()
+    }
+
+    pub fn framework(&self) -> Option<Framework> {
+        self.framework
+    }
+
+    pub fn templ(&self) -> Option<bool> {
+        self.templ
+    }
+
+    pub fn sessions(&self) -> Option<bool> {
+        self.sessions
+    }
+
+    pub fn session_store(&self) -> Option<SessionStore> {
+        self.session_store
+    }
+
+    pub fn database(&self) -> Option<Database> {
+        self.database
+    }
+
+    pub fn auth(&self) -> Option<bool> {
+        self.auth
+    }
+
+    pub fn htmx(&self) -> Option<bool> {
+        self.htmx
+    }
+
+    pub fn tailwind(&self) -> Option<bool> {
+        self.tailwind
+    }
+
+    pub fn air(&self) -> Option<bool> {
+        self.air
+    }
+
+    /// Fills every option that is still unset with the value from `other`,
+    /// so explicitly given values win over e.g. an answers file.
+    pub fn fill_from(self, other: ConfigBuilder) -> Self {
+        ConfigBuilder {
+            app_name: self.app_name.or(other.app_name),
+            module_path: self.module_path.or(other.module_path),
+            framework: self.framework.or(other.framework),
+            templ: self.templ.or(other.templ),
+            sessions: self.sessions.or(other.sessions),
+            session_store: self.session_store.or(other.session_store),
+            database: self.database.or(other.database),
+            auth: self.auth.or(other.auth),
+            htmx: self.htmx.or(other.htmx),
+            htmx_version: self.htmx_version.or(other.htmx_version),
+            htmx_extensions: self.htmx_extensions.or(other.htmx_extensions),
+            tailwind: self.tailwind.or(other.tailwind),
+            air: self.air.or(other.air),
+        }
+    }
+
     pub fn out(self) -> Config {
         Config {
-            app_name: match self.app_name {
-                Some(name) => name,
-                None => "".to_string(),
-            },
-            sessions: match self.sessions {
-                Some(value) => value,
-                None => false,
-            },
-            turso: match self.turso {
-                Some(value) => value,
-                None => false,
-            },
-            htmx: match self.htmx {
-                Some(value) => value,
-                None => false,
-            },
-            tailwind: match self.tailwind {
-                Some(value) => value,
-                None => false,
-            },
-            air: match self.air {
-                Some(value) => value,
-                None => false,
-            },
+            app_name: self.app_name.unwrap_or_default(),
+            module_path: self.module_path,
+            framework: self.framework.unwrap_or_default(),
+            templ: self.templ.unwrap_or_default(),
+            sessions: self.sessions.unwrap_or_default(),
+            session_store: self.session_store.unwrap_or_default(),
+            database: self.database.unwrap_or_default(),
+            auth: self.auth.unwrap_or_default(),
+            htmx: self.htmx.unwrap_or_default(),
+            htmx_version: self
+                .htmx_version
+                .unwrap_or_else(|| htmx::DEFAULT_VERSION.to_string()),
+            htmx_extensions: self.htmx_extensions.unwrap_or_default(),
+            tailwind: self.tailwind.unwrap_or_default(),
+            air: self.air.unwrap_or_default(),
+        }
+    }
+}
+
+/// What the generated app routes requests with.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Framework {
+    #[default]
+    Echo,
+    Chi,
+    /// Plain net/http with the method patterns of Go 1.22's `http.ServeMux`.
+    Stdlib,
+}
+
+impl Framework {
+    pub const ALL: [Framework; 3] = [Framework::Echo, Framework::Chi, Framework::Stdlib];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Framework::Echo => "echo",
+            Framework::Chi => "chi",
+            Framework::Stdlib => "stdlib",
+        }
+    }
+}
+
+/// The gorilla `sessions.Store` the generated app keeps sessions in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SessionStore {
+    /// Everything in a signed cookie.
+    #[default]
+    Cookie,
+    /// Files in the temp dir, with only the id in the cookie.
+    Filesystem,
+    /// A sessions table in the app's database.
+    Db,
+    Redis,
+}
+
+impl SessionStore {
+    pub const ALL: [SessionStore; 4] = [
+        SessionStore::Cookie,
+        SessionStore::Filesystem,
+        SessionStore::Db,
+        SessionStore::Redis,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionStore::Cookie => "cookie",
+            SessionStore::Filesystem => "filesystem",
+            SessionStore::Db => "db",
+            SessionStore::Redis => "redis",
+        }
+    }
+}
+
+/// Where the generated app keeps its data.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Database {
+    #[default]
+    None,
+    /// A local file opened with the pure Go modernc driver.
+    Sqlite,
+    /// A remote libsql database, with a local file during development.
+    Turso,
+    /// Postgres through pgx, with a docker compose service for development.
+    Postgres,
+}
+
+impl Database {
+    pub const ALL: [Database; 4] = [
+        Database::None,
+        Database::Sqlite,
+        Database::Turso,
+        Database::Postgres,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Database::None => "none",
+            Database::Sqlite => "sqlite",
+            Database::Turso => "turso",
+            Database::Postgres => "postgres",
+        }
+    }
+}
+
+/// The optional parts of a project that `vapp add` can turn on later.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    Sessions,
+    Sqlite,
+    Turso,
+    Postgres,
+    Auth,
+    Htmx,
+    Tailwind,
+    Air,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 8] = [
+        Feature::Sessions,
+        Feature::Sqlite,
+        Feature::Turso,
+        Feature::Postgres,
+        Feature::Auth,
+        Feature::Htmx,
+        Feature::Tailwind,
+        Feature::Air,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Sessions => "sessions",
+            Feature::Sqlite => "sqlite",
+            Feature::Turso => "turso",
+            Feature::Postgres => "postgres",
+            Feature::Auth => "auth",
+            Feature::Htmx => "htmx",
+            Feature::Tailwind => "tailwind",
+            Feature::Air => "air",
+        }
+    }
+
+    pub fn is_enabled(&self, config: &Config) -> bool {
+        match self {
+            Feature::Sessions => config.sessions,
+            Feature::Sqlite => config.database == Database::Sqlite,
+            Feature::Turso => config.database == Database::Turso,
+            Feature::Postgres => config.database == Database::Postgres,
+            Feature::Auth => config.auth,
+            Feature::Htmx => config.htmx,
+            Feature::Tailwind => config.tailwind,
+            Feature::Air => config.air,
+        }
+    }
+
+    pub fn enable(&self, config: &mut Config) {
+        match self {
+            Feature::Sessions => config.sessions = true,
+            Feature::Sqlite => config.database = Database::Sqlite,
+            Feature::Turso => config.database = Database::Turso,
+            Feature::Postgres => config.database = Database::Postgres,
+            Feature::Auth => config.auth = true,
+            Feature::Htmx => config.htmx = true,
+            Feature::Tailwind => config.tailwind = true,
+            Feature::Air => config.air = true,
         }
     }
 }
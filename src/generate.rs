@@ -0,0 +1,985 @@
+use std::{collections::BTreeMap, env, error::Error, path::Path};
+
+use crate::{
+    app_builder::{last_migration_number, registration, AppBuilder, MIGRATIONS_DIR},
+    config::{Config, Database, Framework},
+    project::Project,
+    template::{Context, Templates, Value},
+    util::gofmt,
+    validate::validate_files,
+};
+
+type GenerateError = Box<dyn Error>;
+
+pub const METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+pub const GO_KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "chan",
+    "const",
+    "continue",
+    "default",
+    "defer",
+    "else",
+    "fallthrough",
+    "for",
+    "func",
+    "go",
+    "goto",
+    "if",
+    "import",
+    "interface",
+    "map",
+    "package",
+    "range",
+    "return",
+    "select",
+    "struct",
+    "switch",
+    "type",
+    "var",
+];
+
+/// What a resource's variables can't be called on top of `GO_KEYWORDS`:
+/// go's predeclared names, and the packages and locals of the resource
+/// templates.
+const RESERVED_NAMES: &[&str] = &[
+    "any", "append", "bool", "byte", "cap", "clear", "close", "complex", "copy", "delete", "error",
+    "false", "float32", "float64", "imag", "int", "int8", "int16", "int32", "int64", "iota", "len",
+    "make", "max", "min", "new", "nil", "panic", "print", "println", "real", "recover", "rune",
+    "string", "true", "uint", "uint8", "uint16", "uint32", "uint64", "uintptr", "c", "cc", "db",
+    "echo", "err", "http", "id", "redirect", "res", "rows", "sql", "stmt", "strconv", "templ",
+    "views",
+];
+
+/// What a resource's fields can't be called on top of `is_reserved` names,
+/// since they are used as column names unquoted: the keywords sqlite and
+/// postgres reserve.
+const SQL_KEYWORDS: &[&str] = &[
+    "all",
+    "and",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "check",
+    "collate",
+    "column",
+    "constraint",
+    "create",
+    "cross",
+    "current_date",
+    "current_time",
+    "current_timestamp",
+    "default",
+    "delete",
+    "desc",
+    "distinct",
+    "drop",
+    "else",
+    "end",
+    "except",
+    "exists",
+    "foreign",
+    "from",
+    "full",
+    "group",
+    "having",
+    "in",
+    "index",
+    "inner",
+    "insert",
+    "intersect",
+    "into",
+    "is",
+    "join",
+    "key",
+    "left",
+    "like",
+    "limit",
+    "natural",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "outer",
+    "primary",
+    "references",
+    "right",
+    "select",
+    "set",
+    "table",
+    "then",
+    "to",
+    "union",
+    "unique",
+    "update",
+    "user",
+    "using",
+    "values",
+    "when",
+    "where",
+    "with",
+];
+
+/// Adds the route `/<name>` to the project in the current directory: a
+/// handler per method in `internal/routes/<name>.go`, a page in
+/// `public/<name>.html` (or `internal/views/<name>.templ`) and the
+/// registrations in `cmd/<app>/main.go`.
+pub fn generate_route(
+    name: &str,
+    methods: &[String],
+    templates: &Templates,
+) -> Result<(), GenerateError> {
+    if !is_valid_name(name) {
+        return Err(format!(
+            "invalid route name {:?}: use lowercase letters, digits, - and _",
+            name
+        )
+        .into());
+    }
+    let (project, builder) = open_project(templates)?;
+    let generated = route_files(&project.config, &builder, name, methods, templates)?;
+    write_generated(builder.path_to_project(), generated)?;
+    run_rerun_steps(&builder)
+}
+
+/// The files of the route `/<name>` and the main.go registering it.
+fn route_files(
+    config: &Config,
+    builder: &AppBuilder,
+    name: &str,
+    methods: &[String],
+    templates: &Templates,
+) -> Result<Generated, GenerateError> {
+    let root = builder.path_to_project();
+
+    let go_file = format!("internal/routes/{}.go", name);
+    let (page_template, page_file) = page(config.templ, "route", name);
+    check_missing(root, &[&go_file, &page_file])?;
+    let main_file = format!("cmd/{}/main.go", config.app_name);
+    let main_text = read_file(root, &main_file)?;
+
+    let path = format!("/{}", name);
+    let framework = config.framework;
+    let prefix = pascal_case(name);
+    let mut handlers = Vec::new();
+    let mut registrations = Vec::new();
+    for method in methods {
+        let handler_name = format!("{}{}", prefix, pascal_case(method));
+        let mut handler = Context::new();
+        handler.insert("name".to_string(), Value::Str(handler_name.clone()));
+        handler.insert("render".to_string(), Value::Bool(method == "get"));
+        handler.insert("redirect".to_string(), Value::Bool(method == "post"));
+        handlers.push(handler);
+        registrations.push(registration(
+            framework,
+            &method.to_uppercase(),
+            &path,
+            &handler_name,
+        ));
+    }
+    let main_text = insert_registrations(framework, &main_file, &main_text, &registrations)?;
+
+    let mut ctx = builder.template_context();
+    let mut set_str = |key: &str, value: String| ctx.insert(key.to_string(), Value::Str(value));
+    set_str("route", name.to_string());
+    set_str("title", name.to_string());
+    // a post handler redirects to the page, or home when the route has none
+    let has_get = methods.iter().any(|m| m == "get");
+    let redirect_path = if has_get {
+        path.clone()
+    } else {
+        "/".to_string()
+    };
+    set_str("path", path);
+    set_str("redirect_path", redirect_path);
+    set_str("page", format!("{}.html", name));
+    set_str("component", component(name));
+    ctx.insert("handlers".to_string(), Value::List(handlers));
+    // only the get handler renders the page
+    ctx.insert("has_get".to_string(), Value::Bool(has_get));
+    let mut files = BTreeMap::new();
+    files.insert(go_file, templates.render("route_go", &ctx)?);
+    files.insert(page_file, templates.render(&page_template, &ctx)?);
+    return Ok(Generated {
+        files,
+        ctx,
+        main_file,
+        main_text,
+    });
+}
+
+/// Adds the next numbered migration `internal/db/migrations/<n>_<name>.sql`
+/// to the project in the current directory.
+pub fn generate_migration(name: &str, templates: &Templates) -> Result<(), GenerateError> {
+    if !is_valid_identifier(name) {
+        return Err(format!(
+            "invalid migration name {:?}: use lowercase letters, digits and _",
+            name
+        )
+        .into());
+    }
+    let (project, builder) = open_project(templates)?;
+    if project.config.database == Database::None {
+        return Err(
+            "migrations are applied to the database, run `vapp add sqlite`, `vapp add turso` or `vapp add postgres` first"
+                .into(),
+        );
+    }
+    let root = builder.path_to_project();
+    let migration_file = next_migration_file(root, name)?;
+    let mut ctx = builder.template_context();
+    let migration = migration_file.rsplit('/').next().unwrap_or(name);
+    ctx.insert(
+        "migration".to_string(),
+        Value::Str(migration.trim_end_matches(".sql").to_string()),
+    );
+    let mut files = BTreeMap::new();
+    files.insert(migration_file, templates.render("migration_sql", &ctx)?);
+    write_files(root, &files, &ctx)
+}
+
+/// Scaffolds CRUD for a table of `fields` in a project with a database: a
+/// migration creating the table, a model and its queries in `internal/db`, list/show/new/edit
+/// handlers in `internal/routes` and their pages in `public` (or
+/// `internal/views` with templ).
+pub fn generate_resource(
+    name: &str,
+    fields: &[Field],
+    templates: &Templates,
+) -> Result<(), GenerateError> {
+    if !is_valid_identifier(name) {
+        return Err(format!(
+            "invalid resource name {:?}: use lowercase letters, digits and _",
+            name
+        )
+        .into());
+    }
+    if fields.is_empty() {
+        return Err("a resource needs at least one field, e.g. title:string".into());
+    }
+    let (project, builder) = open_project(templates)?;
+    let generated = resource_files(&project.config, &builder, name, fields, templates)?;
+    write_generated(builder.path_to_project(), generated)?;
+    run_rerun_steps(&builder)
+}
+
+/// The files of the resource `name` and the main.go registering its routes.
+fn resource_files(
+    config: &Config,
+    builder: &AppBuilder,
+    name: &str,
+    fields: &[Field],
+    templates: &Templates,
+) -> Result<Generated, GenerateError> {
+    if config.database == Database::None {
+        return Err(
+            "resources are stored in the database, run `vapp add sqlite`, `vapp add turso` or `vapp add postgres` first"
+                .into(),
+        );
+    }
+    let root = builder.path_to_project();
+
+    let plural = pluralize(name);
+    let ctx_package = format!("{}ctx", config.app_name.as_bytes()[0] as char);
+    for var in [camel_case(name), camel_case(&plural)] {
+        if is_reserved(&var) || var == ctx_package {
+            return Err(format!(
+                "invalid resource name {:?}: the generated code would use {} as a variable, which is a go keyword or already taken",
+                name, var
+            )
+            .into());
+        }
+    }
+    let model_file = format!("internal/db/{}.go", plural);
+    let routes_file = format!("internal/routes/{}.go", plural);
+    let form_name = format!("{}_form", name);
+    let templ = config.templ;
+    let pages = [
+        page(templ, "resource_index", &plural),
+        page(templ, "resource_show", name),
+        page(templ, "resource_form", &form_name),
+    ];
+    let mut new_files = vec![&model_file, &routes_file];
+    new_files.extend(pages.iter().map(|(_, file)| file));
+    check_missing(root, &new_files)?;
+    let main_file = format!("cmd/{}/main.go", config.app_name);
+    let main_text = read_file(root, &main_file)?;
+
+    let path = format!("/{}", plural);
+    let framework = config.framework;
+    let handler = |action: &str| format!("{}{}", pascal_case(&plural), action);
+    let register = |method: &str, path: &str, action: &str| {
+        registration(framework, method, path, &handler(action))
+    };
+    let item_path = match framework {
+        Framework::Echo => format!("{}/:id", path),
+        _ => format!("{}/{{id}}", path),
+    };
+    let mut registrations = vec![
+        register("GET", &path, "Index"),
+        register("GET", &format!("{}/new", path), "New"),
+        register("POST", &path, "Create"),
+        register("GET", &item_path, "Show"),
+        register("GET", &format!("{}/edit", item_path), "Edit"),
+        register("POST", &item_path, "Update"),
+    ];
+    if config.htmx {
+        registrations.push(register("DELETE", &item_path, "Delete"));
+    } else {
+        let delete_path = format!("{}/delete", item_path);
+        registrations.push(register("POST", &delete_path, "Delete"));
+    }
+    let main_text = insert_registrations(framework, &main_file, &main_text, &registrations)?;
+    let migration_file = next_migration_file(root, &format!("create_{}", plural))?;
+
+    let var = camel_case(name);
+    let field_list =
+        |f: &dyn Fn(&Field) -> String| fields.iter().map(f).collect::<Vec<String>>().join(", ");
+    let mut ctx = builder.template_context();
+    let mut set_str = |key: &str, value: String| ctx.insert(key.to_string(), Value::Str(value));
+    set_str("title", pascal_case(&plural));
+    set_str("resource", name.to_string());
+    set_str("resources", plural.clone());
+    set_str("Resource", pascal_case(name));
+    set_str("Resources", pascal_case(&plural));
+    set_str("vars", camel_case(&plural));
+    set_str("index_page", format!("{}.html", plural));
+    set_str("show_page", format!("{}.html", name));
+    set_str("form_page", format!("{}.html", form_name));
+    set_str("index_component", component(&plural));
+    set_str("show_component", component(name));
+    set_str("form_component", component(&form_name));
+    set_str("first_field", pascal_case(&fields[0].name));
+    set_str("first_value", fields[0].templ_value(&var));
+    set_str("columns", field_list(&|f| f.name.clone()));
+    let database = config.database;
+    let placeholder = |n: usize| match database {
+        Database::Postgres => format!("${}", n),
+        _ => "?".to_string(),
+    };
+    let numbered = |f: &dyn Fn(usize, &Field) -> String| {
+        let list: Vec<String> = fields
+            .iter()
+            .enumerate()
+            .map(|(i, x)| f(i + 1, x))
+            .collect();
+        list.join(", ")
+    };
+    set_str("placeholders", numbered(&|n, _| placeholder(n)));
+    set_str(
+        "assignments",
+        numbered(&|n, f| format!("{} = {}", f.name, placeholder(n))),
+    );
+    set_str("id_placeholder", placeholder(1));
+    set_str("update_id_placeholder", placeholder(fields.len() + 1));
+    set_str(
+        "scan_fields",
+        field_list(&|f| format!("&{}.{}", var, pascal_case(&f.name))),
+    );
+    set_str(
+        "values",
+        field_list(&|f| format!("{}.{}", var, pascal_case(&f.name))),
+    );
+    set_str("var", var.clone());
+    let field_contexts = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| field.context(i + 1 == fields.len(), database, &var))
+        .collect();
+    ctx.insert("fields".to_string(), Value::List(field_contexts));
+
+    let mut files = BTreeMap::new();
+    files.insert(
+        migration_file,
+        templates.render("resource_migration_sql", &ctx)?,
+    );
+    files.insert(model_file, templates.render("resource_model_go", &ctx)?);
+    files.insert(routes_file, templates.render("resource_routes_go", &ctx)?);
+    let redirect_file = "internal/routes/redirect.go";
+    if !Path::new(&format!("{}/{}", root, redirect_file)).exists() {
+        files.insert(
+            redirect_file.to_string(),
+            templates.render("redirect_go", &ctx)?,
+        );
+    }
+    for (template, file) in &pages {
+        files.insert(file.clone(), templates.render(template, &ctx)?);
+    }
+    return Ok(Generated {
+        files,
+        ctx,
+        main_file,
+        main_text,
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    String,
+    Text,
+    Int,
+    Float,
+    Bool,
+}
+
+impl FieldKind {
+    pub const NAMES: &'static [&'static str] = &["string", "text", "int", "float", "bool"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(Self::String),
+            "text" => Some(Self::Text),
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "bool" => Some(Self::Bool),
+            _ => None,
+        }
+    }
+
+    fn go_type(self) -> &'static str {
+        match self {
+            Self::String | Self::Text => "string",
+            Self::Int => "int64",
+            Self::Float => "float64",
+            Self::Bool => "bool",
+        }
+    }
+
+    fn sql_type(self, database: Database) -> &'static str {
+        match (self, database) {
+            (Self::String | Self::Text, _) => "TEXT",
+            (Self::Int, Database::Postgres) => "BIGINT",
+            (Self::Float, Database::Postgres) => "DOUBLE PRECISION",
+            (Self::Bool, Database::Postgres) => "BOOLEAN",
+            (Self::Int | Self::Bool, _) => "INTEGER",
+            (Self::Float, _) => "REAL",
+        }
+    }
+}
+
+/// A column of a generated resource, given as `name:kind`.
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub kind: FieldKind,
+}
+
+impl Field {
+    pub fn parse(spec: &str) -> Result<Self, GenerateError> {
+        let (name, kind) = match spec.split_once(':') {
+            Some(parts) => parts,
+            None => return Err(format!("expected <name>:<type>, got {}", spec).into()),
+        };
+        if !is_valid_identifier(name) || name == "id" {
+            return Err(format!("invalid field name {:?}", name).into());
+        }
+        if is_reserved(name) || SQL_KEYWORDS.contains(&name) {
+            return Err(format!(
+                "invalid field name {:?}: it is a go or sql keyword or already taken",
+                name
+            )
+            .into());
+        }
+        let kind = match FieldKind::from_name(kind) {
+            Some(kind) => kind,
+            None => {
+                return Err(format!(
+                    "unknown field type {}, expected one of {}",
+                    kind,
+                    FieldKind::NAMES.join(", ")
+                )
+                .into())
+            }
+        };
+        Ok(Self {
+            name: name.to_string(),
+            kind,
+        })
+    }
+
+    /// The field of `var` as a string, for showing it in a templ component.
+    fn templ_value(&self, var: &str) -> String {
+        let value = format!("{}.{}", var, pascal_case(&self.name));
+        match self.kind {
+            FieldKind::String | FieldKind::Text => value,
+            FieldKind::Int => format!("strconv.FormatInt({}, 10)", value),
+            FieldKind::Float => format!("strconv.FormatFloat({}, 'f', -1, 64)", value),
+            FieldKind::Bool => format!("strconv.FormatBool({})", value),
+        }
+    }
+
+    fn context(&self, is_last: bool, database: Database, var: &str) -> Context {
+        let mut ctx = Context::new();
+        let mut set_str =
+            |key: &str, value: &str| ctx.insert(key.to_string(), Value::Str(value.to_string()));
+        set_str("name", &self.name);
+        set_str("Name", &pascal_case(&self.name));
+        set_str("go_type", self.kind.go_type());
+        set_str("sql_type", self.kind.sql_type(database));
+        set_str("comma", if is_last { "" } else { "," });
+        set_str("templ_value", &self.templ_value(var));
+        let input = match self.kind {
+            FieldKind::Int | FieldKind::Float => "number",
+            _ => "text",
+        };
+        set_str("input", input);
+        let kind = self.kind;
+        let flags = [
+            (
+                "string",
+                matches!(kind, FieldKind::String | FieldKind::Text),
+            ),
+            ("textarea", kind == FieldKind::Text),
+            ("int", kind == FieldKind::Int),
+            ("float", kind == FieldKind::Float),
+            ("bool", kind == FieldKind::Bool),
+        ];
+        for (key, value) in flags {
+            ctx.insert(key.to_string(), Value::Bool(value));
+        }
+        return ctx;
+    }
+}
+
+/// The template and file of the page called `name`: `public/<name>.html`,
+/// or with templ `internal/views/<name>.templ` holding its component.
+fn page(templ: bool, template_prefix: &str, name: &str) -> (String, String) {
+    if templ {
+        (
+            format!("{}_templ", template_prefix),
+            format!("internal/views/{}.templ", name),
+        )
+    } else {
+        (
+            format!("{}_html", template_prefix),
+            format!("public/{}.html", name),
+        )
+    }
+}
+
+/// The templ component rendering the page called `name`.
+fn component(name: &str) -> String {
+    format!("{}Page", pascal_case(name))
+}
+
+/// What a generator writes: the new `files`, rendered with `ctx`, and
+/// `main_file` with the new routes registered in `main_text`.
+struct Generated {
+    files: BTreeMap<String, String>,
+    ctx: Context,
+    main_file: String,
+    main_text: String,
+}
+
+fn write_generated(root: &str, generated: Generated) -> Result<(), GenerateError> {
+    write_files(root, &generated.files, &generated.ctx)?;
+    let main_path = format!("{}/{}", root, generated.main_file);
+    std::fs::write(main_path, generated.main_text)?;
+    println!("updated {}", generated.main_file);
+    Ok(())
+}
+
+fn open_project(templates: &Templates) -> Result<(Project, AppBuilder), GenerateError> {
+    let cur_path_buf = env::current_dir()?;
+    let cur_path = match cur_path_buf.to_str() {
+        Some(s) => s.to_string(),
+        None => return Err("cur path is not a valid string".into()),
+    };
+    let project = Project::open(&cur_path)?;
+    let builder = AppBuilder::for_project(project.config.clone(), &project.path, templates)?;
+    return Ok((project, builder));
+}
+
+/// Runs what `vapp add` reruns after changing files, so templ components
+/// are compiled before anything uses them and main.go is formatted.
+fn run_rerun_steps(builder: &AppBuilder) -> Result<(), GenerateError> {
+    for step in builder.steps().into_iter().filter(|s| s.rerun) {
+        builder.run_step(builder.path_to_project(), &step)?;
+    }
+    Ok(())
+}
+
+fn check_missing(root: &str, files: &[&String]) -> Result<(), GenerateError> {
+    for file in files {
+        if Path::new(&format!("{}/{}", root, file)).exists() {
+            return Err(format!("{} already exists", file).into());
+        }
+    }
+    Ok(())
+}
+
+fn read_file(root: &str, file: &str) -> Result<String, GenerateError> {
+    std::fs::read_to_string(format!("{}/{}", root, file))
+        .map_err(|e| format!("could not read {}: {}", file, e).into())
+}
+
+fn write_file(root: &str, file: &str, text: String) -> Result<(), GenerateError> {
+    std::fs::write(format!("{}/{}", root, file), text)?;
+    println!("created {}", file);
+    Ok(())
+}
+
+fn write_go_file(root: &str, file: &str, text: String) -> Result<(), GenerateError> {
+    let text = gofmt(&text).unwrap_or(text);
+    return write_file(root, file, text);
+}
+
+/// Writes the rendered `files`, but only once all of them passed the same
+/// checks a new project does, so a broken user template writes nothing.
+fn write_files(
+    root: &str,
+    files: &BTreeMap<String, String>,
+    ctx: &Context,
+) -> Result<(), GenerateError> {
+    validate_files(files, ctx)?;
+    for (file, text) in files {
+        if file.ends_with(".go") {
+            write_go_file(root, file, text.clone())?;
+        } else {
+            write_file(root, file, text.clone())?;
+        }
+    }
+    Ok(())
+}
+
+/// Inserts `registrations` after the last route registration in `main_text`,
+/// indented the same way.
+fn insert_registrations(
+    framework: Framework,
+    main_file: &str,
+    main_text: &str,
+    registrations: &[String],
+) -> Result<String, GenerateError> {
+    for registration in registrations {
+        if main_text.contains(registration.as_str()) {
+            return Err(format!("{} already registers {}", main_file, registration).into());
+        }
+    }
+    let lines: Vec<&str> = main_text.split_inclusive('\n').collect();
+    let last = lines.iter().rposition(|line| {
+        let line = line.trim_start();
+        METHODS.iter().any(|m| {
+            // everything of the registration up to the path
+            let example = registration(framework, &m.to_uppercase(), "/", "");
+            let prefix = &example[..example.find('/').unwrap_or(0)];
+            line.starts_with(prefix)
+        })
+    });
+    let last = match last {
+        Some(last) => last,
+        None => {
+            return Err(format!(
+                "could not find where {} registers its routes, add these by hand:\n\t{}",
+                main_file,
+                registrations.join("\n\t")
+            )
+            .into())
+        }
+    };
+    let indent = indent_of(lines[last]);
+    let mut res = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        res += line;
+        if i == last {
+            if !line.ends_with('\n') {
+                res.push('\n');
+            }
+            for registration in registrations {
+                res += &format!("{}{}\n", indent, registration);
+            }
+        }
+    }
+    return Ok(res);
+}
+
+/// The path of a new migration called `name`, numbered one past the
+/// highest migration in the project.
+fn next_migration_file(root: &str, name: &str) -> Result<String, GenerateError> {
+    if !Path::new(&format!("{}/{}", root, MIGRATIONS_DIR)).is_dir() {
+        return Err(format!(
+            "{} does not exist, run `vapp upgrade` to add migrations to the project",
+            MIGRATIONS_DIR
+        )
+        .into());
+    }
+    let number = last_migration_number(root) + 1;
+    return Ok(format!("{}/{:04}_{}.sql", MIGRATIONS_DIR, number, name));
+}
+
+fn indent_of(line: &str) -> String {
+    line.chars()
+        .take_while(|c| c.is_whitespace() && *c != '\n')
+        .collect()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn is_valid_identifier(name: &str) -> bool {
+    is_valid_name(name) && !name.contains('-')
+}
+
+fn is_reserved(name: &str) -> bool {
+    GO_KEYWORDS.contains(&name) || RESERVED_NAMES.contains(&name)
+}
+
+/// `post` -> `posts`, `category` -> `categories`, `box` -> `boxes`
+fn pluralize(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix('y') {
+        if !stem.ends_with(['a', 'e', 'i', 'o', 'u']) {
+            return format!("{}ies", stem);
+        }
+    }
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|end| name.ends_with(end))
+    {
+        return format!("{}es", name);
+    }
+    return format!("{}s", name);
+}
+
+/// `user-profile` -> `UserProfile`
+fn pascal_case(name: &str) -> String {
+    let mut res = String::new();
+    for part in name.split(['-', '_']) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            res.extend(first.to_uppercase());
+            res += chars.as_str();
+        }
+    }
+    return res;
+}
+
+/// `blog_post` -> `blogPost`
+fn camel_case(name: &str) -> String {
+    let pascal = pascal_case(name);
+    let mut chars = pascal.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => pascal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+
+    /// A project on disk as `vapp new` would leave it, minus what the steps
+    /// would have added.
+    fn project(name: &str, database: Database) -> (Config, AppBuilder) {
+        let root = env::temp_dir().join(format!("vapp-generate-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let config = ConfigBuilder::new()
+            .add_app_name("app".to_string())
+            .add_module_path("example.com/app".to_string())
+            .add_database(database)
+            .out();
+        let root = root.to_str().unwrap();
+        let builder = AppBuilder::for_project(config.clone(), root, &Templates::default()).unwrap();
+        for dir in builder.dirs() {
+            std::fs::create_dir_all(format!("{}/{}", root, dir)).unwrap();
+        }
+        for (file, text) in builder.files() {
+            std::fs::write(format!("{}/{}", root, file), text).unwrap();
+        }
+        return (config, builder);
+    }
+
+    fn fields(specs: &[&str]) -> Vec<Field> {
+        specs.iter().map(|s| Field::parse(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn generates_a_resource() {
+        let (config, builder) = project("resource", Database::Sqlite);
+        let generated = resource_files(
+            &config,
+            &builder,
+            "category",
+            &fields(&["title:string", "done:bool"]),
+            &Templates::default(),
+        )
+        .unwrap();
+        let files: Vec<&str> = generated.files.keys().map(|f| f.as_str()).collect();
+        assert_eq!(
+            files,
+            [
+                "internal/db/categories.go",
+                "internal/db/migrations/0002_create_categories.sql",
+                "internal/routes/categories.go",
+                "internal/routes/redirect.go",
+                "public/categories.html",
+                "public/category.html",
+                "public/category_form.html",
+            ]
+        );
+        assert_eq!(generated.main_file, "cmd/app/main.go");
+        let index = registration(config.framework, "GET", "/categories", "CategoriesIndex");
+        assert!(generated.main_text.contains(&index));
+        validate_files(&generated.files, &generated.ctx).unwrap();
+        std::fs::remove_dir_all(builder.path_to_project()).unwrap();
+    }
+
+    #[test]
+    fn refuses_resources_without_a_database() {
+        let (config, builder) = project("no-database", Database::None);
+        let fields = fields(&["title:string"]);
+        let res = resource_files(&config, &builder, "post", &fields, &Templates::default());
+        assert!(res.is_err());
+        std::fs::remove_dir_all(builder.path_to_project()).unwrap();
+    }
+
+    #[test]
+    fn refuses_resources_named_like_a_variable() {
+        let (config, builder) = project("reserved", Database::Sqlite);
+        let fields = fields(&["title:string"]);
+        for name in ["err", "new", "actx"] {
+            let res = resource_files(&config, &builder, name, &fields, &Templates::default());
+            assert!(res.is_err(), "{}", name);
+        }
+        std::fs::remove_dir_all(builder.path_to_project()).unwrap();
+    }
+
+    #[test]
+    fn parses_fields() {
+        let field = Field::parse("published_at:int").unwrap();
+        assert_eq!(field.name, "published_at");
+        assert_eq!(field.kind, FieldKind::Int);
+        for spec in [
+            "title",
+            "title:blob",
+            "Title:string",
+            "id:int",
+            "type:string",
+            "err:string",
+            "order:int",
+            "group:string",
+            "select:bool",
+        ] {
+            assert!(Field::parse(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn pluralizes() {
+        for (name, plural) in [
+            ("post", "posts"),
+            ("category", "categories"),
+            ("day", "days"),
+            ("box", "boxes"),
+            ("class", "classes"),
+            ("match", "matches"),
+            ("wish", "wishes"),
+        ] {
+            assert_eq!(pluralize(name), plural);
+        }
+    }
+
+    #[test]
+    fn inserts_registrations_after_the_last_one() {
+        let main =
+            "\te.GET(\"/\", routes.Index)\n\te.GET(\"/about\", routes.About)\n\n\te.Start()\n";
+        let res = insert_registrations(
+            Framework::Echo,
+            "main.go",
+            main,
+            &["e.GET(\"/posts\", routes.PostsIndex)".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            "\te.GET(\"/\", routes.Index)\n\te.GET(\"/about\", routes.About)\n\te.GET(\"/posts\", routes.PostsIndex)\n\n\te.Start()\n"
+        );
+    }
+
+    #[test]
+    fn inserts_registrations_for_every_framework() {
+        let mains = [
+            (
+                Framework::Echo,
+                "\te.GET(\"/\", routes.Index)\n\te.Start()\n",
+            ),
+            (
+                Framework::Chi,
+                "\tr.Get(\"/\", handle(routes.Index))\n\thttp.ListenAndServe()\n",
+            ),
+            (
+                Framework::Stdlib,
+                "\tmux.HandleFunc(\"GET /{$}\", handle(routes.Index))\n\thttp.ListenAndServe()\n",
+            ),
+        ];
+        for (framework, main) in mains {
+            let new = vec![registration(framework, "POST", "/posts", "PostsCreate")];
+            let res = insert_registrations(framework, "main.go", main, &new).unwrap();
+            let lines: Vec<&str> = res.lines().collect();
+            assert_eq!(lines[1], format!("\t{}", new[0]), "{:?}", framework);
+            assert_eq!(lines.len(), 3);
+
+            let err = insert_registrations(framework, "main.go", &res, &new).unwrap_err();
+            assert!(err.to_string().contains("already registers"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn refuses_to_guess_where_registrations_go() {
+        let new = vec![registration(Framework::Chi, "GET", "/posts", "PostsGet")];
+        let main = "\te.GET(\"/\", routes.Index)\n";
+        let err = insert_registrations(Framework::Chi, "main.go", main, &new).unwrap_err();
+        assert!(err.to_string().contains("add these by hand"), "{}", err);
+        assert!(err.to_string().contains(&new[0]), "{}", err);
+    }
+
+    #[test]
+    fn generates_a_route() {
+        let (config, builder) = project("route", Database::None);
+        let methods = vec!["get".to_string(), "post".to_string()];
+        let generated =
+            route_files(&config, &builder, "about", &methods, &Templates::default()).unwrap();
+        let go = &generated.files["internal/routes/about.go"];
+        assert!(go.contains("func AboutGet("), "{}", go);
+        assert!(
+            go.contains("cc.Redirect(http.StatusSeeOther, \"/about\")"),
+            "{}",
+            go
+        );
+        assert!(generated.files.contains_key("public/about.html"));
+        let post = registration(config.framework, "POST", "/about", "AboutPost");
+        assert!(generated.main_text.contains(&post));
+        validate_files(&generated.files, &generated.ctx).unwrap();
+        std::fs::remove_dir_all(builder.path_to_project()).unwrap();
+    }
+
+    #[test]
+    fn post_only_routes_redirect_home() {
+        let (config, builder) = project("post-route", Database::None);
+        let methods = vec!["post".to_string()];
+        let generated = route_files(
+            &config,
+            &builder,
+            "subscribe",
+            &methods,
+            &Templates::default(),
+        )
+        .unwrap();
+        let go = &generated.files["internal/routes/subscribe.go"];
+        assert!(
+            go.contains("cc.Redirect(http.StatusSeeOther, \"/\")"),
+            "{}",
+            go
+        );
+        std::fs::remove_dir_all(builder.path_to_project()).unwrap();
+    }
+}
@@ -0,0 +1 @@
+/* placeholder, the htmx 1.9.12 json-enc extension is not vendored yet: run src/htmx/vendor.sh */
@@ -0,0 +1 @@
+/* placeholder, the htmx 1.9.12 ws extension is not vendored yet: run src/htmx/vendor.sh */
@@ -0,0 +1 @@
+/* placeholder, htmx 1.9.12 is not vendored yet: run src/htmx/vendor.sh */
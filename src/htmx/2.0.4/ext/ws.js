@@ -0,0 +1 @@
+/* placeholder, the htmx 2.0.4 ws extension is not vendored yet: run src/htmx/vendor.sh */
@@ -0,0 +1 @@
+/* placeholder, htmx 2.0.4 is not vendored yet: run src/htmx/vendor.sh */
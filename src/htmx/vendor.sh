@@ -0,0 +1,30 @@
+#!/bin/sh
+# Downloads the htmx versions and extensions vapp ships into src/htmx and
+# prints their sha256, for the pinned hashes in src/htmx.rs.
+set -eu
+cd "$(dirname "$0")"
+
+fetch() {
+	curl -fsSL -o "$1" "$2"
+}
+
+for version in 2.0.4 1.9.12; do
+	mkdir -p "$version/ext"
+	fetch "$version/htmx.min.js" "https://unpkg.com/htmx.org@$version/dist/htmx.min.js"
+	for ext in sse ws json-enc; do
+		case "$version" in
+		1.*) fetch "$version/ext/$ext.js" "https://unpkg.com/htmx.org@$version/dist/ext/$ext.js" ;;
+		*)
+			# htmx 2 moved the extensions into packages of their own
+			case "$ext" in
+			sse) ext_version=2.2.2 ;;
+			ws) ext_version=2.0.3 ;;
+			*) ext_version=2.0.1 ;;
+			esac
+			fetch "$version/ext/$ext.js" "https://unpkg.com/htmx-ext-$ext@$ext_version/$ext.js"
+			;;
+		esac
+	done
+done
+
+sha256sum */htmx.min.js */ext/*.js
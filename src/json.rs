@@ -0,0 +1,318 @@
+/// Just enough JSON to print machine readable output and read back what
+/// vapp itself wrote, without pulling in a serialization crate. Object keys
+/// keep their insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn to_string_pretty(&self) -> String {
+        let mut res = String::new();
+        self.write(&mut res, 0);
+        return res;
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        return Ok(value);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            Json::Null => *out += "null",
+            Json::Bool(b) => *out += &b.to_string(),
+            Json::Int(i) => *out += &i.to_string(),
+            Json::Str(s) => write_str(out, s),
+            Json::Array(items) => {
+                if items.is_empty() {
+                    *out += "[]";
+                    return;
+                }
+                *out += "[\n";
+                for (i, item) in items.iter().enumerate() {
+                    *out += &"  ".repeat(indent + 1);
+                    item.write(out, indent + 1);
+                    if i + 1 < items.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                *out += &"  ".repeat(indent);
+                out.push(']');
+            }
+            Json::Object(fields) => {
+                if fields.is_empty() {
+                    *out += "{}";
+                    return;
+                }
+                *out += "{\n";
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    *out += &"  ".repeat(indent + 1);
+                    write_str(out, key);
+                    *out += ": ";
+                    value.write(out, indent + 1);
+                    if i + 1 < fields.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                *out += &"  ".repeat(indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => *out += "\\\"",
+            '\\' => *out += "\\\\",
+            '\n' => *out += "\\n",
+            '\r' => *out += "\\r",
+            '\t' => *out += "\\t",
+            c if (c as u32) < 0x20 => *out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("invalid json at byte {}: {}", self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", literal)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected , or ]")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(fields));
+                        }
+                        _ => return Err(self.error("expected , or }")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+                self.pos += 1;
+                while let Some(b'0'..=b'9') = self.bytes.get(self.pos) {
+                    self.pos += 1;
+                }
+                let digits = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or("");
+                digits
+                    .parse()
+                    .map(Json::Int)
+                    .map_err(|_| self.error("only integers are supported"))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut res = Vec::new();
+        loop {
+            match self.bytes.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let hex = self
+                                .bytes
+                                .get(self.pos + 1..self.pos + 5)
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .and_then(char::from_u32);
+                            self.pos += 4;
+                            match hex {
+                                Some(c) => c,
+                                None => return Err(self.error("invalid \\u escape")),
+                            }
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0; 4];
+                    res.extend_from_slice(escaped.encode_utf8(&mut buf).as_bytes());
+                    self.pos += 1;
+                }
+                Some(b) => {
+                    res.push(*b);
+                    self.pos += 1;
+                }
+            }
+        }
+        String::from_utf8(res).map_err(|_| self.error("invalid utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_what_it_writes() {
+        let json = Json::Object(vec![
+            ("version".to_string(), Json::Int(-3)),
+            (
+                "name".to_string(),
+                Json::Str("a \"b\"\\\n\tc\u{1}".to_string()),
+            ),
+            (
+                "list".to_string(),
+                Json::Array(vec![Json::Bool(true), Json::Null, Json::Array(Vec::new())]),
+            ),
+            ("empty".to_string(), Json::Object(Vec::new())),
+        ]);
+        assert_eq!(Json::parse(&json.to_string_pretty()), Ok(json));
+    }
+
+    #[test]
+    fn keeps_key_order() {
+        let json = Json::parse(r#"{"b": 1, "a": 2}"#).unwrap();
+        assert_eq!(json.to_string_pretty(), "{\n  \"b\": 1,\n  \"a\": 2\n}");
+        assert_eq!(json.get("a").and_then(|v| v.as_i64()), Some(2));
+    }
+
+    #[test]
+    fn reads_escapes() {
+        let json = Json::parse(r#""\u00e9\/\b""#).unwrap();
+        assert_eq!(json.as_str(), Some("é/\u{8}"));
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        for text in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "\"abc",
+            "1.5",
+            "tru",
+            "{} x",
+            "\"\\x\"",
+        ] {
+            assert!(Json::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
@@ -1,113 +1,264 @@
-use std::process::exit;
+#![allow(clippy::needless_return)]
 
-use config::{Config, ConfigBuilder};
+use std::{
+    error::Error,
+    io::{ErrorKind, IsTerminal},
+    process::exit,
+};
+
+use cli::{Command, NewArgs, USAGE};
+use config::{Config, Database, Framework, SessionStore};
+use template::Templates;
 use util::{read_line, yn_to_bool};
 
 use crate::app_builder::AppBuilder;
 
+mod add;
+mod answers;
 mod app_builder;
+mod cli;
 mod config;
+mod generate;
+mod htmx;
+mod json;
+mod manifest;
+mod merge;
+mod project;
+mod sha;
+mod template;
+mod upgrade;
 mod util;
+mod validate;
 
 fn main() {
-    let config = match build_config() {
+    let command = match cli::parse_args(std::env::args().skip(1)) {
         Ok(c) => c,
         Err(e) => {
-            println!("{:#?}", e);
-            exit(1);
+            eprintln!("error: {}", e);
+            exit(2);
         }
     };
-    let app_builder = match AppBuilder::new(config) {
-        Ok(ab) => ab,
+    let args = match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return;
+        }
+        Command::Add { feature, templates } => {
+            let res = Templates::new(templates.as_deref())
+                .and_then(|templates| add::add_feature(feature, &templates));
+            if let Err(e) = res {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Command::Upgrade { templates } => {
+            let res = Templates::new(templates.as_deref())
+                .and_then(|templates| upgrade::upgrade(&templates));
+            if let Err(e) = res {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Command::GenerateRoute {
+            name,
+            methods,
+            templates,
+        } => {
+            let res = Templates::new(templates.as_deref())
+                .and_then(|templates| generate::generate_route(&name, &methods, &templates));
+            if let Err(e) = res {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Command::GenerateResource {
+            name,
+            fields,
+            templates,
+        } => {
+            let res = Templates::new(templates.as_deref())
+                .and_then(|templates| generate::generate_resource(&name, &fields, &templates));
+            if let Err(e) = res {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Command::GenerateMigration { name, templates } => {
+            let res = Templates::new(templates.as_deref())
+                .and_then(|templates| generate::generate_migration(&name, &templates));
+            if let Err(e) = res {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Command::ExportTemplates(dir) => {
+            if let Err(e) = template::export_templates(&dir) {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Command::New(args) => args,
+    };
+    let templates = match Templates::new(args.templates.as_deref()) {
+        Ok(t) => t,
         Err(e) => {
-            println!("{:#?}", e);
+            eprintln!("error: {}", e);
             exit(1);
         }
     };
-    match app_builder.build() {
-        Ok(_) => (),
+    let dry_run = args.dry_run;
+    let format = args.format;
+    let keep_on_failure = args.keep_on_failure;
+    let save_answers = args.save_answers.clone();
+    let config = match build_config(args) {
+        Ok(c) => c,
         Err(e) => {
-            println!("{:#?}", e);
+            eprintln!("error: {}", e);
             exit(1);
         }
     };
-}
-
-fn build_config() -> std::io::Result<Config> {
-    let app_name: String;
-    loop {
-        let app_name_input = read_line(Some("enter the app name: "))?;
-        if is_valid_app_name(&app_name_input) {
-            app_name = app_name_input;
-            break;
-        }
-        continue;
-    }
-    let sessions: bool;
-    loop {
-        let sessions_input = read_line(Some("would you like to use gorilla sessions? [y/n]: "))?;
-        let yn = yn_to_bool(&sessions_input);
-        match yn {
-            Some(val) => {
-                sessions = val;
-                break;
-            }
-            None => continue,
+    let app_builder = match AppBuilder::new(config.clone(), &templates) {
+        Ok(ab) => ab,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
         }
+    };
+    if dry_run {
+        print!("{}", app_builder.plan(format));
+        return;
     }
-    let turso: bool;
-    loop {
-        let turso_input = read_line(Some("would you like use turso? [y/n]: "))?;
-        let yn = yn_to_bool(&turso_input);
-        match yn {
-            Some(val) => {
-                turso = val;
-                break;
-            }
-            None => continue,
+    // only answers that build a project are worth keeping
+    if let Some(path) = &save_answers {
+        if let Err(e) = answers::write_answers(path, &config) {
+            eprintln!("error: {}", e);
+            exit(1);
         }
     }
-    let htmx: bool;
-    loop {
-        let htmx_input = read_line(Some("would you like use htmx? [y/n]: "))?;
-        let yn = yn_to_bool(&htmx_input);
-        match yn {
-            Some(val) => {
-                htmx = val;
-                break;
-            }
-            None => continue,
+    match app_builder.build(keep_on_failure) {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
         }
+    };
+}
+
+fn build_config(args: NewArgs) -> Result<Config, Box<dyn Error>> {
+    let NewArgs {
+        mut builder,
+        answers,
+        yes,
+        ..
+    } = args;
+    if let Some(path) = &answers {
+        builder = builder.fill_from(answers::read_answers(path)?);
     }
-    let tailwind: bool;
-    loop {
-        let tailwind_input = read_line(Some("would you like use tailwind? [y/n]: "))?;
-        let yn = yn_to_bool(&tailwind_input);
-        match yn {
-            Some(val) => {
-                tailwind = val;
-                break;
-            }
-            None => continue,
+    let interactive = std::io::stdin().is_terminal();
+
+    let app_name = match builder.app_name() {
+        Some(name) if is_valid_app_name(name) => name.to_string(),
+        Some(name) => return Err(invalid_app_name(name).into()),
+        None if yes || !interactive => {
+            return Err("missing app name: pass it as `vapp new <app-name>`".into());
         }
-    }
-    let air: bool;
-    loop {
-        let air_input = read_line(Some("would you like use air? [y/n]: "))?;
-        let yn = yn_to_bool(&air_input);
-        match yn {
-            Some(val) => {
-                air = val;
-                break;
+        None => loop {
+            let app_name_input = read_line(Some("enter the app name: "))?;
+            if is_valid_app_name(&app_name_input) {
+                break app_name_input;
             }
-            None => continue,
-        }
-    }
+            eprintln!("{}", invalid_app_name(&app_name_input));
+        },
+    };
+    let framework = resolve_choice(
+        builder.framework(),
+        "which framework would you like to use? [echo/chi/stdlib]: ",
+        "framework",
+        Framework::from_name,
+        yes,
+        interactive,
+    )?;
+    let templ = resolve_yn(
+        builder.templ(),
+        "would you like to render templ components instead of html templates? [y/n]: ",
+        "templ",
+        yes,
+        interactive,
+    )?;
+    let sessions = resolve_yn(
+        builder.sessions(),
+        "would you like to use gorilla sessions? [y/n]: ",
+        "sessions",
+        yes,
+        interactive,
+    )?;
+    let session_store = if sessions {
+        resolve_choice(
+            builder.session_store(),
+            "where should sessions be stored? [cookie/filesystem/db/redis]: ",
+            "session-store",
+            SessionStore::from_name,
+            yes,
+            interactive,
+        )?
+    } else {
+        SessionStore::default()
+    };
+    let database = resolve_choice(
+        builder.database(),
+        "which database would you like to use? [none/sqlite/turso/postgres]: ",
+        "database",
+        Database::from_name,
+        yes,
+        interactive,
+    )?;
+    let auth = if sessions && database != Database::None {
+        resolve_yn(
+            builder.auth(),
+            "would you like to add register/login/logout? [y/n]: ",
+            "auth",
+            yes,
+            interactive,
+        )?
+    } else {
+        builder.auth().unwrap_or(false)
+    };
+    let htmx = resolve_yn(
+        builder.htmx(),
+        "would you like use htmx? [y/n]: ",
+        "htmx",
+        yes,
+        interactive,
+    )?;
+    let tailwind = resolve_yn(
+        builder.tailwind(),
+        "would you like use tailwind? [y/n]: ",
+        "tailwind",
+        yes,
+        interactive,
+    )?;
+    let air = resolve_yn(
+        builder.air(),
+        "would you like use air? [y/n]: ",
+        "air",
+        yes,
+        interactive,
+    )?;
 
-    let config = ConfigBuilder::new()
+    let config = builder
         .add_app_name(app_name)
+        .add_framework(framework)
+        .add_templ(templ)
         .add_sessions(sessions)
-        .add_turso(turso)
+        .add_session_store(session_store)
+        .add_database(database)
+        .add_auth(auth)
         .add_htmx(htmx)
         .add_tailwind(tailwind)
         .add_air(air)
@@ -115,9 +266,105 @@ fn build_config() -> std::io::Result<Config> {
     return Ok(config);
 }
 
+/// Returns `value` if it was given on the command line, the default (`false`)
+/// when `--yes` was passed, and otherwise asks the user. Asking is an error
+/// when stdin is not a terminal, since nobody is there to answer.
+fn resolve_yn(
+    value: Option<bool>,
+    prompt: &'static str,
+    flag: &str,
+    yes: bool,
+    interactive: bool,
+) -> std::io::Result<bool> {
+    if let Some(val) = value {
+        return Ok(val);
+    }
+    if yes {
+        return Ok(false);
+    }
+    if !interactive {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "stdin is not a terminal and no answer was given for {}: pass --{} or --no-{} (or --yes for the defaults)",
+                flag, flag, flag
+            ),
+        ));
+    }
+    loop {
+        let input = read_line(Some(prompt))?;
+        if let Some(val) = yn_to_bool(&input) {
+            return Ok(val);
+        }
+    }
+}
+
+/// Like `resolve_yn`, for options with more than two values: the default
+/// is `T::default()` and the answer is parsed with `from_name`.
+fn resolve_choice<T: Default>(
+    value: Option<T>,
+    prompt: &'static str,
+    flag: &str,
+    from_name: fn(&str) -> Option<T>,
+    yes: bool,
+    interactive: bool,
+) -> std::io::Result<T> {
+    if let Some(val) = value {
+        return Ok(val);
+    }
+    if yes {
+        return Ok(T::default());
+    }
+    if !interactive {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "stdin is not a terminal and no answer was given for {}: pass --{} <value> (or --yes for the defaults)",
+                flag, flag
+            ),
+        ));
+    }
+    loop {
+        let input = read_line(Some(prompt))?;
+        if let Some(val) = from_name(input.trim()) {
+            return Ok(val);
+        }
+    }
+}
+
+/// Whether `input` can name a project, which makes it the project's
+/// directory, the go package in `cmd/<app-name>` and the end of the
+/// default module path.
 fn is_valid_app_name(input: &str) -> bool {
-    match input {
-        "" => false,
-        _ => true,
+    input.starts_with(|c: char| c.is_ascii_lowercase())
+        && input
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !generate::GO_KEYWORDS.contains(&input)
+        // the root main.go imports the package
+        && input != "main"
+}
+
+fn invalid_app_name(input: &str) -> String {
+    format!(
+        "invalid app name {:?}: it names a go package, use lowercase letters, digits and _ and start with a letter",
+        input
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_names_are_go_packages() {
+        for name in ["app", "my_app", "app2"] {
+            assert!(is_valid_app_name(name), "{}", name);
+        }
+        for name in [
+            "", "../evil", "a/b", "é", "café", "my-app", "App", "2app", "_app", "func", "main",
+        ] {
+            assert!(!is_valid_app_name(name), "{}", name);
+        }
     }
 }
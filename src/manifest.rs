@@ -0,0 +1,293 @@
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use crate::{
+    config::{Config, ConfigBuilder, Database, Framework, SessionStore},
+    json::Json,
+    sha::sha256_hex,
+};
+
+type ManifestError = Box<dyn Error>;
+
+pub const MANIFEST_PATH: &str = ".vapp/manifest.json";
+
+/// Copies of the generated files, the common ancestor `vapp upgrade` merges
+/// the user's edits and the new templates against.
+pub const BASE_DIR: &str = ".vapp/base";
+
+/// Generated files the manifest never records, because they hold secrets
+/// that must not end up in `BASE_DIR`.
+pub const UNTRACKED_FILES: &[&str] = &[".env"];
+
+/// Bumped when the layout of the manifest changes incompatibly. Version 2
+/// replaced the `turso` flag of the config with `database`.
+pub const MANIFEST_VERSION: i64 = 2;
+
+/// What vapp generated for a project: the config it was generated from and
+/// a hash of every file as vapp left it, so later commands can tell which
+/// files the user has edited since.
+///
+/// `.vapp/` is meant to be committed with the project: without it a fresh
+/// clone can't be upgraded, and `vapp add` has no base to merge against.
+/// It holds no secrets, since `UNTRACKED_FILES` are never recorded.
+#[derive(Debug)]
+pub struct Manifest {
+    pub vapp_version: String,
+    pub mod_name: String,
+    pub config: Config,
+    pub files: BTreeMap<String, String>,
+    /// Generated contents recorded since the manifest was loaded, written
+    /// to `BASE_DIR` together with the manifest.
+    bases: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    pub fn new(config: Config, mod_name: String) -> Self {
+        Self {
+            vapp_version: env!("CARGO_PKG_VERSION").to_string(),
+            mod_name,
+            config,
+            files: BTreeMap::new(),
+            bases: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, file: &str, content: &str) {
+        if UNTRACKED_FILES.contains(&file) {
+            return;
+        }
+        self.files
+            .insert(file.to_string(), format!("sha256:{}", hash(content)));
+        self.bases.insert(file.to_string(), content.to_string());
+    }
+
+    /// Keeps an earlier record of `file` whose base copy is already on disk.
+    pub fn keep(&mut self, file: &str, recorded_hash: &str) {
+        self.files
+            .insert(file.to_string(), recorded_hash.to_string());
+        self.bases.remove(file);
+    }
+
+    /// The generated contents of `file` as last recorded, if the project
+    /// has a copy of them.
+    pub fn read_base(path_to_project: &str, file: &str) -> Option<String> {
+        std::fs::read_to_string(format!("{}/{}/{}", path_to_project, BASE_DIR, file)).ok()
+    }
+
+    /// Whether `content` is exactly what vapp wrote to `file`.
+    pub fn is_unchanged(&self, file: &str, content: &str) -> bool {
+        match self.files.get(file) {
+            Some(recorded) => *recorded == format!("sha256:{}", hash(content)),
+            None => false,
+        }
+    }
+
+    pub fn read(path_to_project: &str) -> Result<Option<Self>, ManifestError> {
+        let path = format!("{}/{}", path_to_project, MANIFEST_PATH);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path)?;
+        let json = Json::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let manifest = Self::from_json(&json).map_err(|e| format!("{}: {}", path, e))?;
+        return Ok(Some(manifest));
+    }
+
+    pub fn write(&self, path_to_project: &str) -> Result<(), ManifestError> {
+        for (file, content) in self.contents() {
+            let path = Path::new(path_to_project).join(file);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, content)?;
+        }
+        Ok(())
+    }
+
+    /// What `write` writes, relative to the project: the base copies
+    /// followed by the manifest itself.
+    pub fn contents(&self) -> Vec<(String, String)> {
+        let mut res: Vec<(String, String)> = self
+            .bases
+            .iter()
+            .map(|(file, content)| (format!("{}/{}", BASE_DIR, file), content.clone()))
+            .collect();
+        res.push((
+            MANIFEST_PATH.to_string(),
+            self.to_json().to_string_pretty() + "\n",
+        ));
+        return res;
+    }
+
+    fn to_json(&self) -> Json {
+        let mut config = vec![(
+            "app_name".to_string(),
+            Json::Str(self.config.app_name.clone()),
+        )];
+        if let Some(module_path) = &self.config.module_path {
+            config.push(("module_path".to_string(), Json::Str(module_path.clone())));
+        }
+        config.push((
+            "framework".to_string(),
+            Json::Str(self.config.framework.name().to_string()),
+        ));
+        config.push((
+            "session_store".to_string(),
+            Json::Str(self.config.session_store.name().to_string()),
+        ));
+        config.push((
+            "database".to_string(),
+            Json::Str(self.config.database.name().to_string()),
+        ));
+        config.push((
+            "htmx_version".to_string(),
+            Json::Str(self.config.htmx_version.clone()),
+        ));
+        config.push((
+            "htmx_extensions".to_string(),
+            Json::Array(
+                self.config
+                    .htmx_extensions
+                    .iter()
+                    .map(|name| Json::Str(name.clone()))
+                    .collect(),
+            ),
+        ));
+        let flags = [
+            ("templ", self.config.templ),
+            ("sessions", self.config.sessions),
+            ("auth", self.config.auth),
+            ("htmx", self.config.htmx),
+            ("tailwind", self.config.tailwind),
+            ("air", self.config.air),
+        ];
+        for (key, value) in flags {
+            config.push((key.to_string(), Json::Bool(value)));
+        }
+        let files = self
+            .files
+            .iter()
+            .map(|(file, hash)| (file.clone(), Json::Str(hash.clone())))
+            .collect();
+        return Json::Object(vec![
+            ("manifest_version".to_string(), Json::Int(MANIFEST_VERSION)),
+            (
+                "vapp_version".to_string(),
+                Json::Str(self.vapp_version.clone()),
+            ),
+            ("mod_name".to_string(), Json::Str(self.mod_name.clone())),
+            ("config".to_string(), Json::Object(config)),
+            ("files".to_string(), Json::Object(files)),
+        ]);
+    }
+
+    fn from_json(json: &Json) -> Result<Self, ManifestError> {
+        match json.get("manifest_version").and_then(|v| v.as_i64()) {
+            Some(v) if (1..=MANIFEST_VERSION).contains(&v) => (),
+            Some(v) => {
+                return Err(format!(
+                    "manifest version {} is newer than this vapp supports ({})",
+                    v, MANIFEST_VERSION
+                )
+                .into())
+            }
+            None => return Err("missing manifest_version".into()),
+        }
+        let str_field = |key: &str| -> Result<String, ManifestError> {
+            match json.get(key).and_then(|v| v.as_str()) {
+                Some(s) => Ok(s.to_string()),
+                None => Err(format!("missing {}", key).into()),
+            }
+        };
+        let vapp_version = str_field("vapp_version")?;
+        let mod_name = str_field("mod_name")?;
+
+        let config_json = match json.get("config") {
+            Some(c) => c,
+            None => return Err("missing config".into()),
+        };
+        let mut builder = ConfigBuilder::new();
+        if let Some(name) = config_json.get("app_name").and_then(|v| v.as_str()) {
+            builder = builder.add_app_name(name.to_string());
+        }
+        if let Some(path) = config_json.get("module_path").and_then(|v| v.as_str()) {
+            builder = builder.add_module_path(path.to_string());
+        }
+        // manifests from before the framework choice are all echo projects
+        if let Some(name) = config_json.get("framework").and_then(|v| v.as_str()) {
+            match Framework::from_name(name) {
+                Some(framework) => builder = builder.add_framework(framework),
+                None => return Err(format!("unknown framework {}", name).into()),
+            }
+        }
+        let flag = |key: &str| config_json.get(key).and_then(|v| v.as_bool());
+        if let Some(v) = flag("templ") {
+            builder = builder.add_templ(v);
+        }
+        if let Some(v) = flag("sessions") {
+            builder = builder.add_sessions(v);
+        }
+        if let Some(name) = config_json.get("session_store").and_then(|v| v.as_str()) {
+            match SessionStore::from_name(name) {
+                Some(store) => builder = builder.add_session_store(store),
+                None => return Err(format!("unknown session store {}", name).into()),
+            }
+        }
+        if let Some(name) = config_json.get("database").and_then(|v| v.as_str()) {
+            match Database::from_name(name) {
+                Some(database) => builder = builder.add_database(database),
+                None => return Err(format!("unknown database {}", name).into()),
+            }
+        } else if let Some(v) = flag("turso") {
+            let database = if v { Database::Turso } else { Database::None };
+            builder = builder.add_database(database);
+        }
+        if let Some(v) = flag("auth") {
+            builder = builder.add_auth(v);
+        }
+        if let Some(v) = flag("htmx") {
+            builder = builder.add_htmx(v);
+        }
+        // manifests from before htmx was vendored leave these to the defaults
+        if let Some(version) = config_json.get("htmx_version").and_then(|v| v.as_str()) {
+            builder = builder.add_htmx_version(version.to_string());
+        }
+        if let Some(Json::Array(items)) = config_json.get("htmx_extensions") {
+            let extensions = items
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|name| name.to_string())
+                .collect();
+            builder = builder.add_htmx_extensions(extensions);
+        }
+        if let Some(v) = flag("tailwind") {
+            builder = builder.add_tailwind(v);
+        }
+        if let Some(v) = flag("air") {
+            builder = builder.add_air(v);
+        }
+        let config = builder.out();
+        if config.app_name.is_empty() {
+            return Err("missing config.app_name".into());
+        }
+
+        let mut files = BTreeMap::new();
+        if let Some(Json::Object(entries)) = json.get("files") {
+            for (file, hash) in entries {
+                if let Some(hash) = hash.as_str() {
+                    files.insert(file.clone(), hash.to_string());
+                }
+            }
+        }
+        return Ok(Self {
+            vapp_version,
+            mod_name,
+            config,
+            files,
+            bases: BTreeMap::new(),
+        });
+    }
+}
+
+fn hash(content: &str) -> String {
+    sha256_hex(content.as_bytes())
+}
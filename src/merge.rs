@@ -0,0 +1,177 @@
+/// The result of a three-way merge.
+#[derive(Debug)]
+pub struct Merged {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`, line by
+/// line, the way `diff3 -m` does. Hunks changed differently on both sides
+/// are kept as conflicts between `<<<<<<<`/`=======`/`>>>>>>>` markers.
+pub fn merge3(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    ours_label: &str,
+    theirs_label: &str,
+) -> Merged {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let ours_match = match_lines(&base, &ours);
+    let theirs_match = match_lines(&base, &theirs);
+
+    let mut text = String::new();
+    let mut conflicts = 0;
+    let (mut o, mut a, mut b) = (0, 0, 0);
+    while o < base.len() || a < ours.len() || b < theirs.len() {
+        if o < base.len() && ours_match[o] == Some(a) && theirs_match[o] == Some(b) {
+            text += base[o];
+            o += 1;
+            a += 1;
+            b += 1;
+            continue;
+        }
+        // the next line of base both sides kept ends the changed hunk
+        let mut next_o = o;
+        while next_o < base.len()
+            && (ours_match[next_o].is_none() || theirs_match[next_o].is_none())
+        {
+            next_o += 1;
+        }
+        let (next_a, next_b) = if next_o < base.len() {
+            (
+                ours_match[next_o].unwrap_or(ours.len()),
+                theirs_match[next_o].unwrap_or(theirs.len()),
+            )
+        } else {
+            (ours.len(), theirs.len())
+        };
+        let base_hunk = &base[o..next_o];
+        let ours_hunk = &ours[a..next_a];
+        let theirs_hunk = &theirs[b..next_b];
+        if ours_hunk == base_hunk || ours_hunk == theirs_hunk {
+            push_lines(&mut text, theirs_hunk);
+        } else if theirs_hunk == base_hunk {
+            push_lines(&mut text, ours_hunk);
+        } else {
+            conflicts += 1;
+            push_marker(&mut text, &format!("<<<<<<< {}", ours_label));
+            push_lines(&mut text, ours_hunk);
+            push_marker(&mut text, "=======");
+            push_lines(&mut text, theirs_hunk);
+            push_marker(&mut text, &format!(">>>>>>> {}", theirs_label));
+        }
+        o = next_o;
+        a = next_a;
+        b = next_b;
+    }
+    return Merged { text, conflicts };
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        *out += line;
+    }
+}
+
+fn push_marker(out: &mut String, marker: &str) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    *out += marker;
+    out.push('\n');
+}
+
+/// For every line of `base`, the index of the line of `other` it is paired
+/// with in a longest common subsequence of the two, if any.
+fn match_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let (n, m) = (base.len(), other.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if base[i] == other[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut res = vec![None; n];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            res[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> Merged {
+        merge3(base, ours, theirs, "ours", "theirs")
+    }
+
+    #[test]
+    fn changes_to_different_lines_merge_cleanly() {
+        let merged = merge("a\nb\nc\nd\n", "A\nb\nc\nd\n", "a\nb\nc\nD\n");
+        assert_eq!(merged.text, "A\nb\nc\nD\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn the_same_change_on_both_sides_is_no_conflict() {
+        let merged = merge("a\nb\nc\n", "a\nB\nc\n", "a\nB\nc\n");
+        assert_eq!(merged.text, "a\nB\nc\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn different_changes_to_the_same_lines_conflict() {
+        let merged = merge("a\nb\nc\n", "a\nmine\nc\n", "a\nyours\nc\n");
+        assert_eq!(
+            merged.text,
+            "a\n<<<<<<< ours\nmine\n=======\nyours\n>>>>>>> theirs\nc\n"
+        );
+        assert_eq!(merged.conflicts, 1);
+    }
+
+    #[test]
+    fn insertions_on_both_sides() {
+        let merged = merge("a\nb\nc\n", "a\nx\nb\nc\n", "a\nb\nc\ny\n");
+        assert_eq!(merged.text, "a\nx\nb\nc\ny\n");
+        assert_eq!(merged.conflicts, 0);
+
+        let merged = merge("a\nb\n", "a\nx\nb\n", "a\ny\nb\n");
+        assert_eq!(
+            merged.text,
+            "a\n<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\nb\n"
+        );
+        assert_eq!(merged.conflicts, 1);
+    }
+
+    #[test]
+    fn deletions_merge_with_unrelated_edits() {
+        let merged = merge("a\nb\nc\nd\n", "a\nc\nd\n", "a\nb\nc\nD\n");
+        assert_eq!(merged.text, "a\nc\nD\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn a_missing_final_newline_still_gets_markers_on_their_own_line() {
+        let merged = merge("a\nb", "a\nmine", "a\nyours");
+        assert_eq!(
+            merged.text,
+            "a\n<<<<<<< ours\nmine\n=======\nyours\n>>>>>>> theirs\n"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::{error::Error, path::Path};
+
+use crate::{
+    config::{Config, ConfigBuilder, Database, Framework, SessionStore},
+    htmx,
+    manifest::Manifest,
+};
+
+type ProjectError = Box<dyn Error>;
+
+/// A project vapp generated earlier. Its config comes from the manifest
+/// when there is one, and is otherwise worked out from the files.
+#[derive(Debug)]
+pub struct Project {
+    pub path: String,
+    pub config: Config,
+    pub manifest: Option<Manifest>,
+}
+
+impl Project {
+    pub fn open(path: &str) -> Result<Self, ProjectError> {
+        if let Some(manifest) = Manifest::read(path)? {
+            let mut config = manifest.config.clone();
+            if config.module_path.is_none() {
+                config.module_path = Some(manifest.mod_name.clone());
+            }
+            return Ok(Self {
+                path: path.to_string(),
+                config,
+                manifest: Some(manifest),
+            });
+        }
+        let config = detect_config(path)?;
+        return Ok(Self {
+            path: path.to_string(),
+            config,
+            manifest: None,
+        });
+    }
+}
+
+/// Works out the config of a project without a manifest from its files.
+fn detect_config(path: &str) -> Result<Config, ProjectError> {
+    let go_mod = std::fs::read_to_string(format!("{}/go.mod", path))
+        .map_err(|_| format!("{} is not a go project (no go.mod)", path))?;
+    let mod_name = match go_mod
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("module "))
+    {
+        Some(name) => name.trim().to_string(),
+        None => return Err("go.mod has no module line".into()),
+    };
+    let app_name = find_app_name(path)?;
+    let first_letter = app_name.as_bytes()[0] as char;
+    let custom_ctx_name = format!("{}ctx", first_letter);
+    let custom_ctx = std::fs::read_to_string(format!(
+        "{}/internal/{}/{}.go",
+        path, custom_ctx_name, custom_ctx_name
+    ))
+    .map_err(|_| format!("{} does not look like a vapp project", path))?;
+    // where the htmx script tags live, depending on the project's age and renderer
+    let head: String = [
+        "public/partials/head.html",
+        "public/index.html",
+        "internal/views/layout.templ",
+    ]
+    .iter()
+    .filter_map(|file| std::fs::read_to_string(format!("{}/{}", path, file)).ok())
+    .collect();
+    let uses_htmx = head.contains("htmx");
+    // vendored htmx carries its version, otherwise it is in the unpkg url
+    let htmx_js =
+        std::fs::read_to_string(format!("{}/public/js/htmx.min.js", path)).unwrap_or_default();
+    let htmx_version = htmx::VERSIONS
+        .iter()
+        .find(|version| {
+            htmx_js.contains(&format!("\"{}\"", version))
+                || head.contains(&format!("htmx.org@{}/", version))
+        })
+        .unwrap_or(&htmx::DEFAULT_VERSION);
+    let htmx_extensions = htmx::EXTENSIONS
+        .iter()
+        .filter(|name| head.contains(&format!("/{}.js", name)))
+        .map(|name| name.to_string())
+        .collect();
+    let main_go =
+        std::fs::read_to_string(format!("{}/cmd/{}/main.go", path, app_name)).unwrap_or_default();
+    let framework = if custom_ctx.contains("github.com/labstack/echo") {
+        Framework::Echo
+    } else if main_go.contains("github.com/go-chi/chi") {
+        Framework::Chi
+    } else {
+        Framework::Stdlib
+    };
+    let session_store = if main_go.contains("NewFilesystemStore") {
+        SessionStore::Filesystem
+    } else if main_go.contains("NewSessionStore") {
+        SessionStore::Db
+    } else if main_go.contains("redisstore") {
+        SessionStore::Redis
+    } else {
+        SessionStore::Cookie
+    };
+    let database = match std::fs::read_to_string(format!("{}/internal/db/db.go", path)) {
+        Ok(db_go) if db_go.contains("pgx") => Database::Postgres,
+        Ok(db_go) if db_go.contains("libsql") => Database::Turso,
+        Ok(_) => Database::Sqlite,
+        Err(_) => Database::None,
+    };
+
+    let config = ConfigBuilder::new()
+        .add_app_name(app_name)
+        .add_module_path(mod_name)
+        .add_framework(framework)
+        .add_templ(Path::new(&format!("{}/internal/views", path)).is_dir())
+        .add_sessions(custom_ctx.contains("github.com/gorilla/sessions"))
+        .add_session_store(session_store)
+        .add_database(database)
+        .add_auth(Path::new(&format!("{}/internal/routes/auth.go", path)).exists())
+        .add_htmx(uses_htmx)
+        .add_htmx_version(htmx_version.to_string())
+        .add_htmx_extensions(htmx_extensions)
+        .add_tailwind(Path::new(&format!("{}/css/index.css", path)).exists())
+        .add_air(Path::new(&format!("{}/.air.toml", path)).exists())
+        .out();
+    return Ok(config);
+}
+
+/// The app name is the one directory under `cmd/`.
+fn find_app_name(path: &str) -> Result<String, ProjectError> {
+    let cmd_dir = format!("{}/cmd", path);
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(&cmd_dir)
+        .map_err(|_| format!("{} does not look like a vapp project (no cmd dir)", path))?
+    {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    match names.len() {
+        1 => Ok(names.remove(0)),
+        0 => Err(format!("no app found in {}", cmd_dir).into()),
+        _ => Err(format!("more than one app in {}: {}", cmd_dir, names.join(", ")).into()),
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+type TemplateError = Box<dyn Error>;
+
+/// The values a template can refer to. `##if##` treats an empty string or
+/// list as false, so optional values don't need a separate flag.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Str(String),
+    Bool(bool),
+    List(Vec<Context>),
+}
+
+pub type Context = BTreeMap<String, Value>;
+
+/// Every template shipped with vapp, by name.
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("air_toml", include_str!("text/air_toml")),
+    ("auth_routes_go", include_str!("text/auth_routes_go")),
+    ("auth_templ", include_str!("text/auth_templ")),
+    ("base_layout_html", include_str!("text/base_layout_html")),
+    ("cmd_main_go", include_str!("text/cmd_main_go")),
+    ("csrf_go", include_str!("text/csrf_go")),
+    ("ctx_auth_go", include_str!("text/ctx_auth_go")),
+    ("custom_ctx_go", include_str!("text/custom_ctx_go")),
+    ("db_go", include_str!("text/db_go")),
+    (
+        "docker_compose_yml",
+        include_str!("text/docker_compose_yml"),
+    ),
+    ("env", include_str!("text/env")),
+    ("env_go", include_str!("text/env_go")),
+    ("gitignore", include_str!("text/gitignore")),
+    ("head_html", include_str!("text/head_html")),
+    ("index_css", include_str!("text/index_css")),
+    ("index_html", include_str!("text/index_html")),
+    ("index_templ", include_str!("text/index_templ")),
+    ("init_sql", include_str!("text/init_sql")),
+    ("layout_templ", include_str!("text/layout_templ")),
+    ("login_html", include_str!("text/login_html")),
+    ("main_go", include_str!("text/main_go")),
+    ("makefile", include_str!("text/makefile")),
+    ("migrate_go", include_str!("text/migrate_go")),
+    ("migration_sql", include_str!("text/migration_sql")),
+    ("public_go", include_str!("text/public_go")),
+    ("redirect_go", include_str!("text/redirect_go")),
+    ("register_html", include_str!("text/register_html")),
+    ("render_go", include_str!("text/render_go")),
+    (
+        "resource_form_html",
+        include_str!("text/resource_form_html"),
+    ),
+    (
+        "resource_form_templ",
+        include_str!("text/resource_form_templ"),
+    ),
+    (
+        "resource_index_html",
+        include_str!("text/resource_index_html"),
+    ),
+    (
+        "resource_index_templ",
+        include_str!("text/resource_index_templ"),
+    ),
+    (
+        "resource_migration_sql",
+        include_str!("text/resource_migration_sql"),
+    ),
+    ("resource_model_go", include_str!("text/resource_model_go")),
+    (
+        "resource_routes_go",
+        include_str!("text/resource_routes_go"),
+    ),
+    (
+        "resource_show_html",
+        include_str!("text/resource_show_html"),
+    ),
+    (
+        "resource_show_templ",
+        include_str!("text/resource_show_templ"),
+    ),
+    ("root_go", include_str!("text/root_go")),
+    ("route_go", include_str!("text/route_go")),
+    ("route_html", include_str!("text/route_html")),
+    ("route_templ", include_str!("text/route_templ")),
+    ("session_store_go", include_str!("text/session_store_go")),
+    ("users_go", include_str!("text/users_go")),
+    (
+        "users_migration_sql",
+        include_str!("text/users_migration_sql"),
+    ),
+    ("views_csrf_go", include_str!("text/views_csrf_go")),
+];
+
+pub fn builtin_template(name: &str) -> Option<&'static str> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, text)| *text)
+}
+
+/// Where templates are loaded from. A template is taken from the first
+/// directory that has a file with its name, falling back to the copy built
+/// into vapp, so overriding e.g. `index_html` doesn't mean copying the rest.
+#[derive(Debug, Default)]
+pub struct Templates {
+    dirs: Vec<PathBuf>,
+}
+
+impl Templates {
+    /// Looks in `templates_dir` (from `--templates`), then in
+    /// `~/.config/vapp/templates`, then in the built-in templates.
+    pub fn new(templates_dir: Option<&str>) -> Result<Self, TemplateError> {
+        let mut dirs = Vec::new();
+        if let Some(dir) = templates_dir {
+            if !Path::new(dir).is_dir() {
+                return Err(format!("templates directory {} does not exist", dir).into());
+            }
+            dirs.push(PathBuf::from(dir));
+        }
+        if let Some(dir) = user_templates_dir() {
+            if dir.is_dir() {
+                dirs.push(dir);
+            }
+        }
+        return Ok(Self { dirs });
+    }
+
+    pub fn get(&self, name: &str) -> Result<String, TemplateError> {
+        for dir in &self.dirs {
+            let path = dir.join(name);
+            if path.is_file() {
+                return std::fs::read_to_string(&path).map_err(|e| {
+                    format!("failed to read template {}: {}", path.display(), e).into()
+                });
+            }
+        }
+        match builtin_template(name) {
+            Some(text) => Ok(text.to_string()),
+            None => Err(format!("unknown template {}", name).into()),
+        }
+    }
+
+    /// Renders the template `name` with `ctx`.
+    ///
+    /// Templates are plain text with `##...##` markers:
+    ///
+    /// - `##var##` is replaced with a string value, `##item.var##` reads a
+    ///   field of the current `##each##` item
+    /// - `##if flag##` ... `##else##` ... `##end##` keeps one of the branches,
+    ///   `##if !flag##` negates the test
+    /// - `##each item in list##` ... `##end##` repeats its body for every item
+    /// - `##include name##` renders another template in place
+    ///
+    /// A block marker that is alone on its line removes the whole line, so
+    /// conditionals don't leave blank lines behind.
+    pub fn render(&self, name: &str, ctx: &Context) -> Result<String, TemplateError> {
+        let mut scopes = vec![ctx.clone()];
+        let mut res = String::new();
+        self.render_template(name, &mut scopes, &mut Vec::new(), &mut res)?;
+        return Ok(res);
+    }
+
+    /// Renders `name` into `out`. `includes` are the templates being
+    /// rendered around it, outermost first, so an include cycle is an error
+    /// rather than a stack overflow.
+    fn render_template(
+        &self,
+        name: &str,
+        scopes: &mut Vec<Context>,
+        includes: &mut Vec<String>,
+        out: &mut String,
+    ) -> Result<(), TemplateError> {
+        if includes.iter().any(|n| n == name) {
+            let mut cycle = includes.clone();
+            cycle.push(name.to_string());
+            return Err(format!("include cycle: {}", cycle.join(" -> ")).into());
+        }
+        let text = self.get(name)?;
+        let nodes = parse_template(&text).map_err(|e| format!("{}: {}", name, e))?;
+        includes.push(name.to_string());
+        let res = self.render_nodes(&nodes, scopes, includes, out);
+        includes.pop();
+        res.map_err(|e| format!("{}: {}", name, e).into())
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        scopes: &mut Vec<Context>,
+        includes: &mut Vec<String>,
+        out: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => *out += text,
+                Node::Var(name, line) => match lookup(scopes, name) {
+                    Some(Value::Str(s)) => *out += s,
+                    Some(Value::Bool(b)) => *out += &b.to_string(),
+                    Some(Value::List(_)) => {
+                        return Err(format!("line {}: ##{}## is a list", line, name).into())
+                    }
+                    None => return Err(format!("line {}: unknown value ##{}##", line, name).into()),
+                },
+                Node::If {
+                    cond,
+                    negate,
+                    then,
+                    otherwise,
+                } => {
+                    let value = match lookup(scopes, cond) {
+                        Some(value) => is_truthy(value),
+                        None => return Err(format!("unknown value {} in ##if##", cond).into()),
+                    };
+                    if value != *negate {
+                        self.render_nodes(then, scopes, includes, out)?;
+                    } else {
+                        self.render_nodes(otherwise, scopes, includes, out)?;
+                    }
+                }
+                Node::Each { item, list, body } => {
+                    let items = match lookup(scopes, list) {
+                        Some(Value::List(items)) => items.clone(),
+                        Some(_) => return Err(format!("{} in ##each## is not a list", list).into()),
+                        None => return Err(format!("unknown value {} in ##each##", list).into()),
+                    };
+                    for entry in items {
+                        let scope = entry
+                            .into_iter()
+                            .map(|(key, value)| (format!("{}.{}", item, key), value))
+                            .collect();
+                        scopes.push(scope);
+                        let res = self.render_nodes(body, scopes, includes, out);
+                        scopes.pop();
+                        res?;
+                    }
+                }
+                Node::Include(name) => {
+                    self.render_template(name, scopes, includes, out)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+fn user_templates_dir() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    return Some(config_dir.join("vapp").join("templates"));
+}
+
+/// Writes every built-in template into `dir` as a starting point for
+/// overriding them. Existing files are never overwritten.
+pub fn export_templates(dir: &str) -> Result<(), TemplateError> {
+    std::fs::create_dir_all(dir)?;
+    for (name, _) in BUILTIN_TEMPLATES {
+        let path = Path::new(dir).join(name);
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()).into());
+        }
+    }
+    for (name, text) in BUILTIN_TEMPLATES {
+        std::fs::write(Path::new(dir).join(name), text)?;
+        println!("wrote {}/{}", dir, name);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Tag(&'a str, usize),
+}
+
+#[derive(Debug)]
+enum Node<'a> {
+    Text(&'a str),
+    Var(&'a str, usize),
+    If {
+        cond: &'a str,
+        negate: bool,
+        then: Vec<Node<'a>>,
+        otherwise: Vec<Node<'a>>,
+    },
+    Each {
+        item: &'a str,
+        list: &'a str,
+        body: Vec<Node<'a>>,
+    },
+    Include(&'a str),
+}
+
+fn is_block_tag(tag: &str) -> bool {
+    tag == "else"
+        || tag == "end"
+        || tag.starts_with("if ")
+        || tag.starts_with("each ")
+        || tag.starts_with("include ")
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    let mut line = 1;
+    while let Some(start) = rest.find("##") {
+        let after = &rest[start + 2..];
+        let end = match after.find("##") {
+            Some(end) => end,
+            None => return Err(format!("line {}: unclosed ## marker", line).into()),
+        };
+        let tag = after[..end].trim();
+        let mut before = &rest[..start];
+        let mut next = &after[end + 2..];
+        line += before.matches('\n').count();
+
+        if is_block_tag(tag) {
+            let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let at_line_start = before[line_start..].trim().is_empty()
+                && (line_start > 0 || tokens.is_empty() || ends_with_newline(&tokens));
+            let line_end = next.find('\n');
+            let rest_of_line = match line_end {
+                Some(i) => &next[..i],
+                None => next,
+            };
+            if at_line_start && rest_of_line.trim().is_empty() {
+                before = &before[..line_start];
+                next = match line_end {
+                    Some(i) => &next[i + 1..],
+                    None => "",
+                };
+                line += 1;
+            }
+        }
+        if !before.is_empty() {
+            tokens.push(Token::Text(before));
+        }
+        tokens.push(Token::Tag(tag, line));
+        rest = next;
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    return Ok(tokens);
+}
+
+fn ends_with_newline(tokens: &[Token<'_>]) -> bool {
+    match tokens.last() {
+        Some(Token::Text(t)) => t.ends_with('\n'),
+        // a standalone block tag consumed its own newline
+        Some(Token::Tag(tag, _)) => is_block_tag(tag),
+        None => true,
+    }
+}
+
+/// How a nested `parse` call stopped.
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Else,
+    End,
+    Eof,
+}
+
+/// Parses tokens until the `##else##`/`##end##` closing the current block,
+/// or until the end of the template at the top level.
+fn parse<'a, I>(tokens: &mut I) -> Result<(Vec<Node<'a>>, Stop), TemplateError>
+where
+    I: Iterator<Item = Token<'a>>,
+{
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        let (tag, line) = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Token::Tag(tag, line) => (tag, line),
+        };
+        if tag == "else" {
+            return Ok((nodes, Stop::Else));
+        }
+        if tag == "end" {
+            return Ok((nodes, Stop::End));
+        }
+        if let Some(cond) = tag.strip_prefix("if ") {
+            let cond = cond.trim();
+            let (cond, negate) = match cond.strip_prefix('!') {
+                Some(c) => (c.trim(), true),
+                None => (cond, false),
+            };
+            let (then, end) = parse(tokens)?;
+            let otherwise = match end {
+                Stop::Else => match parse(tokens)? {
+                    (otherwise, Stop::End) => otherwise,
+                    _ => return Err(format!("line {}: unclosed ##if {}##", line, cond).into()),
+                },
+                Stop::End => Vec::new(),
+                Stop::Eof => return Err(format!("line {}: unclosed ##if {}##", line, cond).into()),
+            };
+            nodes.push(Node::If {
+                cond,
+                negate,
+                then,
+                otherwise,
+            });
+        } else if let Some(spec) = tag.strip_prefix("each ") {
+            let (item, list) = match spec.split_once(" in ") {
+                Some((item, list)) => (item.trim(), list.trim()),
+                None => {
+                    return Err(format!("line {}: expected ##each <item> in <list>##", line).into())
+                }
+            };
+            let body = match parse(tokens)? {
+                (body, Stop::End) => body,
+                _ => return Err(format!("line {}: unclosed ##each {}##", line, spec).into()),
+            };
+            nodes.push(Node::Each { item, list, body });
+        } else if let Some(name) = tag.strip_prefix("include ") {
+            nodes.push(Node::Include(name.trim()));
+        } else {
+            nodes.push(Node::Var(tag, line));
+        }
+    }
+    return Ok((nodes, Stop::Eof));
+}
+
+fn parse_template(text: &str) -> Result<Vec<Node<'_>>, TemplateError> {
+    let tokens = tokenize(text)?;
+    match parse(&mut tokens.into_iter())? {
+        (nodes, Stop::Eof) => Ok(nodes),
+        (_, _) => Err("##else## or ##end## without a matching block".into()),
+    }
+}
+
+fn lookup<'s>(scopes: &'s [Context], name: &str) -> Option<&'s Value> {
+    scopes.iter().rev().find_map(|scope| scope.get(name))
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Str(s) => !s.is_empty(),
+        Value::Bool(b) => *b,
+        Value::List(items) => !items.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str, ctx: &Context) -> Result<String, TemplateError> {
+        let nodes = parse_template(text)?;
+        let mut out = String::new();
+        let mut scopes = vec![ctx.clone()];
+        Templates::default().render_nodes(&nodes, &mut scopes, &mut Vec::new(), &mut out)?;
+        return Ok(out);
+    }
+
+    fn ctx(values: &[(&str, Value)]) -> Context {
+        values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn standalone_tags_remove_their_line() {
+        let ctx = ctx(&[("a", Value::Bool(true)), ("b", Value::Bool(false))]);
+        let text = "one\n##if a##\ntwo\n##end##\n##if b##\nthree\n##end##\nfour\n";
+        assert_eq!(render(text, &ctx).unwrap(), "one\ntwo\nfour\n");
+    }
+
+    #[test]
+    fn inline_tags_keep_their_line() {
+        let ctx = ctx(&[("a", Value::Bool(true)), ("x", Value::Str("y".into()))]);
+        let text = "<body##if a## class=\"##x##\"##end##>\n";
+        assert_eq!(render(text, &ctx).unwrap(), "<body class=\"y\">\n");
+    }
+
+    #[test]
+    fn negated_if_and_else() {
+        let ctx = ctx(&[("a", Value::Bool(false)), ("s", Value::Str(String::new()))]);
+        let text = "##if !a##\nnot a\n##else##\na\n##end##\n##if s##\ns\n##else##\nno s\n##end##\n";
+        assert_eq!(render(text, &ctx).unwrap(), "not a\nno s\n");
+    }
+
+    #[test]
+    fn each_scopes_item_fields() {
+        let item = |name: &str| ctx(&[("name", Value::Str(name.to_string()))]);
+        let ctx = ctx(&[
+            ("name", Value::Str("outer".to_string())),
+            ("items", Value::List(vec![item("a"), item("b")])),
+        ]);
+        let text = "##each item in items##\n##item.name## ##name##\n##end##\n";
+        assert_eq!(render(text, &ctx).unwrap(), "a outer\nb outer\n");
+        assert!(render("##each item in items####end####item.name##", &ctx).is_err());
+    }
+
+    #[test]
+    fn unknown_values_are_errors() {
+        assert!(render("##missing##", &Context::new()).is_err());
+        assert!(render("##if missing##x##end##", &Context::new()).is_err());
+    }
+
+    #[test]
+    fn unclosed_blocks_are_errors() {
+        let ctx = ctx(&[("a", Value::Bool(true)), ("items", Value::List(Vec::new()))]);
+        assert!(render("##if a##\nx\n", &ctx).is_err());
+        assert!(render("##if a##\nx\n##else##\ny\n", &ctx).is_err());
+        assert!(render("##each item in items##\nx\n", &ctx).is_err());
+        assert!(render("x\n##end##\n", &ctx).is_err());
+        assert!(render("x ##a", &ctx).is_err());
+    }
+
+    #[test]
+    fn builtin_templates_parse() {
+        for (name, text) in BUILTIN_TEMPLATES {
+            assert!(parse_template(text).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let dir = std::env::temp_dir().join(format!("vapp-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a"), "a\n##include b##\n").unwrap();
+        std::fs::write(dir.join("b"), "b\n##include a##\n").unwrap();
+        std::fs::write(dir.join("c"), "##include d##\n##include d##\n").unwrap();
+        std::fs::write(dir.join("d"), "d\n").unwrap();
+        let templates = Templates::new(dir.to_str()).unwrap();
+        let err = templates.render("a", &Context::new()).unwrap_err();
+        assert!(
+            err.to_string().ends_with("include cycle: a -> b -> a"),
+            "{}",
+            err
+        );
+        assert_eq!(templates.render("c", &Context::new()).unwrap(), "d\nd\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+use std::{env, error::Error};
+
+use crate::{
+    app_builder::AppBuilder, manifest::Manifest, merge::merge3, project::Project,
+    template::Templates, util::append_missing_env_keys,
+};
+
+type UpgradeError = Box<dyn Error>;
+
+/// Regenerates the project in the current directory from its recorded
+/// config with the current templates, and merges the result into the
+/// project: changes to the templates since the project was generated are
+/// applied on top of the user's own edits, with conflict markers where
+/// both touched the same lines.
+pub fn upgrade(templates: &Templates) -> Result<(), UpgradeError> {
+    let cur_path_buf = env::current_dir()?;
+    let cur_path = match cur_path_buf.to_str() {
+        Some(s) => s.to_string(),
+        None => return Err("cur path is not a valid string".into()),
+    };
+    let project = Project::open(&cur_path)?;
+    if project.manifest.is_none() {
+        return Err(format!(
+            "{} has no .vapp/manifest.json, so there is no record of what vapp generated to upgrade from",
+            cur_path
+        )
+        .into());
+    }
+    let new = AppBuilder::for_project(project.config, &project.path, templates)?;
+    let root = new.path_to_project();
+
+    let temp_dir = env::temp_dir().join(format!("vapp-upgrade-{}", std::process::id()));
+    let temp_root = match temp_dir.to_str() {
+        Some(s) => s.to_string(),
+        None => return Err("temp path is not a valid string".into()),
+    };
+    if temp_dir.exists() {
+        std::fs::remove_dir_all(&temp_dir)?;
+    }
+    let res = new
+        .generate_into(&temp_root)
+        .and_then(|_| merge_files(&new, &temp_root));
+    // best effort, a leftover temp dir only takes up space and must not
+    // hide why the upgrade failed
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    let conflicts = res?;
+
+    if conflicts.is_empty() {
+        for step in new.steps().into_iter().filter(|s| s.rerun) {
+            new.run_step(root, &step)?;
+        }
+        println!("upgraded to vapp {}", env!("CARGO_PKG_VERSION"));
+    } else {
+        println!("these files have conflicts between your changes and the new templates:");
+        for (file, count) in &conflicts {
+            println!("    {} ({} conflicts)", file, count);
+        }
+        println!("resolve them, then run `go mod tidy` and `go fmt ./...`");
+    }
+    Ok(())
+}
+
+/// Merges every file generated in `temp_root` into the project, returning
+/// the files left with conflicts and how many each has.
+fn merge_files(new: &AppBuilder, temp_root: &str) -> Result<Vec<(String, usize)>, UpgradeError> {
+    let root = new.path_to_project();
+    for dir in new.dirs() {
+        std::fs::create_dir_all(format!("{}/{}", root, dir))?;
+    }
+    let mut conflicts = Vec::new();
+    for file in new.files().keys() {
+        let path = format!("{}/{}", root, file);
+        let theirs = std::fs::read_to_string(format!("{}/{}", temp_root, file))?;
+        let current = std::fs::read_to_string(&path).ok();
+        let base = Manifest::read_base(root, file);
+        let (current, base) = match (current, base) {
+            (Some(current), base) => (current, base.unwrap_or_default()),
+            (None, None) => {
+                std::fs::write(&path, &theirs)?;
+                println!("created {}", file);
+                continue;
+            }
+            (None, Some(_)) => {
+                println!("skipped {} (deleted)", file);
+                continue;
+            }
+        };
+        if file == ".env" {
+            // never recorded, so there is no base to merge against
+            let updated = append_missing_env_keys(&current, &theirs);
+            if updated != current {
+                std::fs::write(&path, updated)?;
+                println!("updated {}", file);
+            }
+            continue;
+        }
+        if current == theirs || base == theirs {
+            continue;
+        }
+        if current == base {
+            std::fs::write(&path, &theirs)?;
+            println!("updated {}", file);
+            continue;
+        }
+        let merged = merge3(&base, &current, &theirs, "yours", "vapp");
+        std::fs::write(&path, &merged.text)?;
+        if merged.conflicts > 0 {
+            conflicts.push((file.clone(), merged.conflicts));
+        } else {
+            println!("merged {}", file);
+        }
+    }
+    new.manifest(temp_root).write(root)?;
+    return Ok(conflicts);
+}
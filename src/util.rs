@@ -1,16 +1,13 @@
 use std::{
     io::{Error, Write},
-    process::{Command, ExitStatus},
+    process::{Command, ExitStatus, Stdio},
 };
 
 pub fn read_line(prompt: Option<&'static str>) -> std::io::Result<String> {
-    match prompt {
-        Some(p) => {
-            print!("{}", p);
-            std::io::stdout().flush()?;
-        }
-        None => (),
-    };
+    if let Some(p) = prompt {
+        print!("{}", p);
+        std::io::stdout().flush()?;
+    }
     let mut res = String::new();
     let _ = std::io::stdin().read_line(&mut res)?;
     return Ok(res.trim().to_string());
@@ -69,10 +66,7 @@ pub fn get_exit_code(exit_status: Result<ExitStatus, Error>) -> i32 {
     match exit_status {
         Ok(code) => {
             if !code.success() {
-                match code.code() {
-                    Some(value) => value,
-                    None => -1,
-                }
+                code.code().unwrap_or(-1)
             } else {
                 0
             }
@@ -80,3 +74,90 @@ pub fn get_exit_code(exit_status: Result<ExitStatus, Error>) -> i32 {
         _ => -1,
     }
 }
+
+/// Formats go source with `gofmt`, or returns `None` if gofmt isn't
+/// available or the source doesn't parse.
+pub fn gofmt(source: &str) -> Option<String> {
+    let mut child = Command::new("gofmt")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(source.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    return String::from_utf8(output.stdout).ok();
+}
+
+/// `bytes` random bytes from the OS, hex encoded.
+pub fn random_hex(bytes: usize) -> std::io::Result<String> {
+    let mut buf = vec![0u8; bytes];
+    fill_random(&mut buf)?;
+    let mut res = String::with_capacity(bytes * 2);
+    for byte in buf {
+        res += &format!("{:02x}", byte);
+    }
+    return Ok(res);
+}
+
+#[cfg(unix)]
+fn fill_random(buf: &mut [u8]) -> std::io::Result<()> {
+    use std::io::Read;
+    std::fs::File::open("/dev/urandom")?.read_exact(buf)
+}
+
+// vapp has no dependencies to reach the OS's random number generator with,
+// and only unix has it as a file
+#[cfg(not(unix))]
+fn fill_random(_buf: &mut [u8]) -> std::io::Result<()> {
+    Err(Error::new(
+        std::io::ErrorKind::Unsupported,
+        "generating secrets needs /dev/urandom, vapp only supports unix-like systems",
+    ))
+}
+
+/// `data` in standard, padded base64.
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut res = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    return res;
+}
+
+/// Adds the `KEY=value` lines of `wanted` whose key `current` doesn't set
+/// yet, leaving the values already there alone.
+pub fn append_missing_env_keys(current: &str, wanted: &str) -> String {
+    let key = |line: &str| line.split_once('=').map(|(k, _)| k.trim().to_string());
+    let present: Vec<String> = current.lines().filter_map(key).collect();
+    let mut res = current.to_string();
+    for line in wanted.lines() {
+        match key(line) {
+            Some(k) if !present.contains(&k) => {
+                if !res.is_empty() && !res.ends_with('\n') {
+                    res.push('\n');
+                }
+                res += line;
+                res.push('\n');
+            }
+            _ => (),
+        }
+    }
+    return res;
+}
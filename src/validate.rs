@@ -0,0 +1,207 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use crate::template::{Context, Value};
+
+/// Names the templates were originally written against. Any of these
+/// showing up in a generated project that isn't called `weather` means a
+/// template hardcodes them instead of using a placeholder.
+const ARTIFACTS: &[(&str, &str, &str)] = &[
+    ("package weather", "name", "weather"),
+    ("weather.Main(", "name", "weather"),
+    ("/cmd/weather\"", "name", "weather"),
+    ("wctx.", "ctx", "wctx"),
+    ("package wctx", "ctx", "wctx"),
+    ("/internal/wctx\"", "ctx", "wctx"),
+    ("WCtx", "Ctx", "WCtx"),
+];
+
+#[derive(Debug)]
+pub struct Problem {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ValidationError {
+    pub problems: Vec<Problem>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "generated files contain template leftovers:")?;
+        for problem in &self.problems {
+            write!(
+                f,
+                "\n    {}:{}: {}",
+                problem.file, problem.line, problem.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Checks every generated file for unresolved `##...##` placeholders and
+/// for hardcoded template artifacts. `values` are the placeholder values
+/// used for this project, so an app that really is called `weather` passes.
+pub fn validate_files(
+    files: &BTreeMap<String, String>,
+    values: &Context,
+) -> Result<(), ValidationError> {
+    let mut problems = Vec::new();
+    for (file, content) in files {
+        for (i, line) in content.lines().enumerate() {
+            if let Some(placeholder) = find_placeholder(line) {
+                problems.push(Problem {
+                    file: file.clone(),
+                    line: i + 1,
+                    message: format!("unresolved placeholder {}", placeholder),
+                });
+            }
+            for (artifact, key, original) in ARTIFACTS {
+                let is_original = match values.get(*key) {
+                    Some(Value::Str(value)) => value == original,
+                    _ => false,
+                };
+                if !is_original && line.contains(artifact) {
+                    problems.push(Problem {
+                        file: file.clone(),
+                        line: i + 1,
+                        message: format!("hardcoded template artifact {:?}", artifact),
+                    });
+                }
+            }
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError { problems })
+    }
+}
+
+/// Returns the first `##word##` marker in `line`. Only markers that look
+/// like template names count, so e.g. markdown headings are left alone.
+fn find_placeholder(line: &str) -> Option<&str> {
+    let mut rest = line;
+    let mut offset = 0;
+    while let Some(start) = rest.find("##") {
+        let after = &rest[start + 2..];
+        let end = after.find("##")?;
+        let inner = after[..end].trim();
+        let is_name = !inner.is_empty()
+            && inner
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == ' ' || c == '!');
+        if is_name {
+            let from = offset + start;
+            return Some(&line[from..from + end + 4]);
+        }
+        offset += start + 2;
+        rest = after;
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app_builder::AppBuilder,
+        config::{ConfigBuilder, Database, Framework, SessionStore},
+        htmx,
+        template::Templates,
+    };
+
+    /// Every config vapp accepts renders into files without leftovers.
+    /// Building the app runs `validate_files`, so each one only has to build.
+    #[test]
+    fn every_config_validates() {
+        let templates = Templates::default();
+        let all_extensions: Vec<String> = htmx::EXTENSIONS.iter().map(|e| e.to_string()).collect();
+        // every htmx version, without extensions and with all of them
+        let htmx_variants: Vec<(&str, Vec<String>)> = htmx::VERSIONS
+            .iter()
+            .flat_map(|v| [(*v, Vec::new()), (*v, all_extensions.clone())])
+            .collect();
+        // None is sessions off
+        let stores = std::iter::once(None).chain(SessionStore::ALL.into_iter().map(Some));
+        let mut built = 0;
+        for framework in Framework::ALL {
+            for database in Database::ALL {
+                for store in stores.clone() {
+                    // templ, auth, htmx, tailwind and air
+                    for flags in 0..32 {
+                        let flag = |i: u32| flags & (1 << i) != 0;
+                        let has_db = database != Database::None;
+                        if store == Some(SessionStore::Db) && !has_db {
+                            continue;
+                        }
+                        if flag(1) && !(store.is_some() && has_db) {
+                            continue;
+                        }
+                        // the htmx version and extensions only matter with htmx
+                        let variants = if flag(2) {
+                            &htmx_variants[..]
+                        } else {
+                            &htmx_variants[..1]
+                        };
+                        for (version, extensions) in variants {
+                            let config = ConfigBuilder::new()
+                                .add_app_name("app".to_string())
+                                .add_module_path("example.com/app".to_string())
+                                .add_framework(framework)
+                                .add_templ(flag(0))
+                                .add_sessions(store.is_some())
+                                .add_session_store(store.unwrap_or_default())
+                                .add_database(database)
+                                .add_auth(flag(1))
+                                .add_htmx(flag(2))
+                                .add_htmx_version(version.to_string())
+                                .add_htmx_extensions(extensions.clone())
+                                .add_tailwind(flag(3))
+                                .add_air(flag(4))
+                                .out();
+                            let described = format!("{:?}", config);
+                            if let Err(e) =
+                                AppBuilder::for_project(config, "/nonexistent/app", &templates)
+                            {
+                                panic!("{}\n{}", described, e);
+                            }
+                            built += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(built > 0);
+    }
+
+    #[test]
+    fn finds_unresolved_placeholders() {
+        let mut files = BTreeMap::new();
+        files.insert("a.go".to_string(), "ok\nname := ##name##\n".to_string());
+        let err = validate_files(&files, &Context::new()).unwrap_err();
+        assert_eq!(err.problems.len(), 1);
+        assert_eq!(err.problems[0].line, 2);
+    }
+
+    #[test]
+    fn allows_artifacts_the_project_really_has() {
+        let mut files = BTreeMap::new();
+        files.insert("main.go".to_string(), "package weather\n".to_string());
+        let mut values = Context::new();
+        assert!(validate_files(&files, &values).is_err());
+        values.insert("name".to_string(), Value::Str("weather".to_string()));
+        assert!(validate_files(&files, &values).is_ok());
+    }
+
+    #[test]
+    fn ignores_markdown_headings() {
+        assert_eq!(find_placeholder("## Usage"), None);
+        assert_eq!(find_placeholder("## Usage: run it ##"), None);
+        assert_eq!(find_placeholder("a ## b"), None);
+    }
+}